@@ -0,0 +1,349 @@
+use std::{
+	fs::File,
+	io::{self, BufRead, BufReader, Cursor, Read, Seek},
+};
+
+use zip::ZipArchive;
+
+use crate::{stream::Stream, wprintln};
+
+/// Offset of the magic bytes in the header of a tar archive.
+const TAR_MAGIC_OFFSET: usize = 257;
+const TAR_MAGIC: &[u8] = b"ustar";
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+
+/// Represents an archive, whose members are counted as seperate streams.
+pub enum Archive<'a> {
+	/// Represents a tar archive, possibly compressed.
+	///
+	/// First element is the tar reader, the second is the label of the archive.
+	Tar(tar::Archive<Stream<'a>>, String),
+	/// Represents a zip archive.
+	///
+	/// First element is the stream of the archive, the second is the label of the archive.
+	/// Zip archives need to be seekable, so the ones that can't be seeked, like pipes and
+	/// compressed streams, are read into the memory first.
+	Zip(Stream<'a>, String),
+}
+
+impl<'a> Archive<'a> {
+	/// Creates an `Archive` from the `stream` if it is a tar or zip archive. If not, `stream` is
+	/// returned back.
+	pub fn from_stream(mut stream: Stream<'a>) -> Result<Self, Stream<'a>> {
+		let label = stream.label();
+
+		let (is_zip, is_tar) = match stream.fill_buf() {
+			Ok(head) => (
+				head.starts_with(ZIP_MAGIC),
+				head.get(TAR_MAGIC_OFFSET..TAR_MAGIC_OFFSET + TAR_MAGIC.len())
+					== Some(TAR_MAGIC),
+			),
+			// Reading errors are reported when the stream is counted.
+			Err(_) => return Err(stream),
+		};
+
+		if is_zip {
+			Ok(Archive::Zip(stream, label))
+		} else if is_tar {
+			Ok(Archive::Tar(tar::Archive::new(stream), label))
+		} else {
+			Err(stream)
+		}
+	}
+
+	/// Returns the label of the `Archive`.
+	#[inline]
+	pub fn label(&self) -> &str {
+		match self {
+			Archive::Tar(_, s) | Archive::Zip(_, s) => s,
+		}
+	}
+
+	/// Calls `f` with a `Stream` for every regular file in the archive.
+	///
+	/// Returns `None` if any of the members couldn't be read, or `f` returned `None` for any of
+	/// them. If `werror` is set, stops at the first one.
+	pub fn for_each_member<F>(self, werror: bool, mut f: F) -> Option<()>
+	where
+		F: FnMut(Stream<'_>) -> Option<()>,
+	{
+		match self {
+			Archive::Tar(mut archive, label) => {
+				let entries = match archive.entries() {
+					Ok(entries) => entries,
+					Err(e) => {
+						wprintln!("{}: {}", label, e);
+						return None;
+					}
+				};
+
+				let mut res = Some(());
+
+				for entry in entries {
+					let member = entry.and_then(|e| {
+						if !e.header().entry_type().is_file() {
+							return Ok(None);
+						}
+
+						let path = e.path()?.to_string_lossy().into_owned();
+
+						Ok(Some((path, e)))
+					});
+
+					let counted = match member {
+						Ok(Some((path, entry))) => Self::count_member(
+							&label,
+							&path,
+							BufReader::new(entry),
+							&mut f,
+						),
+						Ok(None) => Some(()),
+						// Position in the archive is unknown after an error, can't continue.
+						Err(e) => {
+							wprintln!("{}: {}", label, e);
+							return None;
+						}
+					};
+
+					if counted.is_none() {
+						if werror {
+							return None;
+						}
+						res = None;
+					}
+				}
+
+				res
+			}
+			Archive::Zip(Stream::File(reader, _), label) => {
+				Self::zip_members(reader, &label, werror, f)
+			}
+			Archive::Zip(Stream::Stdin(stdin), label) => match seekable_stdin() {
+				Some(file) => Self::zip_members(file, &label, werror, f),
+				None => Self::buffered_zip_members(
+					Stream::Stdin(stdin),
+					&label,
+					werror,
+					f,
+				),
+			},
+			Archive::Zip(stream, label) => {
+				Self::buffered_zip_members(stream, &label, werror, f)
+			}
+		}
+	}
+
+	/// Reads the zip archive in `stream` into the memory, and calls `f` with a `Stream` for every
+	/// regular file in it.
+	fn buffered_zip_members<F>(
+		mut stream: Stream<'a>,
+		label: &str,
+		werror: bool,
+		f: F,
+	) -> Option<()>
+	where
+		F: FnMut(Stream<'_>) -> Option<()>,
+	{
+		let mut content = Vec::new();
+
+		if let Err(e) = stream.read_to_end(&mut content) {
+			wprintln!("{}: {}", label, e);
+			return None;
+		}
+
+		Self::zip_members(Cursor::new(content), label, werror, f)
+	}
+
+	/// Calls `f` with a `Stream` for every regular file in the zip archive read from `reader`.
+	fn zip_members<R, F>(reader: R, label: &str, werror: bool, mut f: F) -> Option<()>
+	where
+		R: Read + Seek,
+		F: FnMut(Stream<'_>) -> Option<()>,
+	{
+		let mut archive = match ZipArchive::new(reader) {
+			Ok(archive) => archive,
+			Err(e) => {
+				wprintln!("{}: {}", label, e);
+				return None;
+			}
+		};
+
+		let mut res = Some(());
+
+		for i in 0..archive.len() {
+			let counted = match archive.by_index(i) {
+				Ok(file) if file.is_file() => {
+					let path = file.name().to_string();
+					Self::count_member(
+						label,
+						&path,
+						BufReader::new(file),
+						&mut f,
+					)
+				}
+				Ok(_) => Some(()),
+				Err(e) => {
+					wprintln!("{}: {}", label, e);
+					None
+				}
+			};
+
+			if counted.is_none() {
+				if werror {
+					return None;
+				}
+				res = None;
+			}
+		}
+
+		res
+	}
+
+	/// Creates the `Stream` of a member and calls `f` with it.
+	#[inline]
+	fn count_member<R, F>(label: &str, path: &str, reader: R, f: &mut F) -> Option<()>
+	where
+		R: BufRead,
+		F: FnMut(Stream<'_>) -> Option<()>,
+	{
+		Stream::from_member(reader, format!("{}:{}", label, path)).and_then(f)
+	}
+}
+
+/// Returns the standard input as a `File`, if it is redirected from a regular file, so it can be
+/// seeked.
+#[cfg(unix)]
+fn seekable_stdin() -> Option<File> {
+	use std::os::fd::AsFd;
+
+	let file = File::from(io::stdin().as_fd().try_clone_to_owned().ok()?);
+	file.metadata().ok()?.is_file().then_some(file)
+}
+
+/// Returns the standard input as a `File`, if it is redirected from a regular file, so it can be
+/// seeked.
+#[cfg(windows)]
+fn seekable_stdin() -> Option<File> {
+	use std::os::windows::io::AsHandle;
+
+	let file = File::from(io::stdin().as_handle().try_clone_to_owned().ok()?);
+	file.metadata().ok()?.is_file().then_some(file)
+}
+
+/// Returns the standard input as a `File`, if it is redirected from a regular file, so it can be
+/// seeked.
+#[cfg(not(any(unix, windows)))]
+fn seekable_stdin() -> Option<File> {
+	None
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+	use std::io::Write;
+
+	use zip::{write::SimpleFileOptions, ZipWriter};
+
+	use super::*;
+	use crate::args::InputEncoding;
+
+	/// Returns a tar archive with a directory, `dir/a.txt` of `lorem ipsum` and `b.txt` of
+	/// `dolor`.
+	pub(crate) fn tar_fixture() -> Vec<u8> {
+		let mut builder = tar::Builder::new(Vec::new());
+
+		let mut append = |path: &str, kind: tar::EntryType, content: &[u8]| {
+			let mut header = tar::Header::new_ustar();
+			header.set_entry_type(kind);
+			header.set_size(content.len() as u64);
+			header.set_mode(0o644);
+			builder.append_data(&mut header, path, content).unwrap();
+		};
+
+		append("dir", tar::EntryType::Directory, b"");
+		append("dir/a.txt", tar::EntryType::Regular, b"lorem ipsum");
+		append("b.txt", tar::EntryType::Regular, b"dolor");
+
+		builder.into_inner().unwrap()
+	}
+
+	fn zip_fixture() -> Vec<u8> {
+		let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+		let options = SimpleFileOptions::default();
+
+		writer.add_directory("dir/", options).unwrap();
+		writer.start_file("dir/a.txt", options).unwrap();
+		writer.write_all(b"lorem ipsum").unwrap();
+		writer.add_symlink("link", "dir/a.txt", options).unwrap();
+		writer.start_file("b.txt", options).unwrap();
+		writer.write_all(b"dolor").unwrap();
+
+		writer.finish().unwrap().into_inner()
+	}
+
+	/// Returns the label and the content of every member in `archive`.
+	fn members(archive: Archive) -> Vec<(String, String)> {
+		let mut res = Vec::new();
+
+		archive.for_each_member(false, |mut s| {
+			let mut content = String::new();
+			s.read_to_string(InputEncoding::Utf8, &mut content)?;
+			res.push((s.label(), content));
+			Some(())
+		})
+		.unwrap();
+
+		res
+	}
+
+	fn expected() -> Vec<(String, String)> {
+		vec![
+			(String::from("test:dir/a.txt"), String::from("lorem ipsum")),
+			(String::from("test:b.txt"), String::from("dolor")),
+		]
+	}
+
+	#[test]
+	fn tar() {
+		let content = tar_fixture();
+		let stream = Stream::from_member(&content[..], String::from("test")).unwrap();
+		let archive = Archive::from_stream(stream).ok().unwrap();
+
+		assert!(matches!(archive, Archive::Tar(..)));
+		assert_eq!(members(archive), expected());
+	}
+
+	#[test]
+	fn zip() {
+		let content = zip_fixture();
+		let stream = Stream::from_member(&content[..], String::from("test")).unwrap();
+		let archive = Archive::from_stream(stream).ok().unwrap();
+
+		assert!(matches!(archive, Archive::Zip(..)));
+		assert_eq!(members(archive), expected());
+	}
+
+	#[test]
+	fn zip_file() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("test.zip");
+		std::fs::write(&path, zip_fixture()).unwrap();
+		let path = path.to_string_lossy().into_owned();
+
+		let stream = Stream::from_str(&path).unwrap();
+		let archive = Archive::from_stream(stream).ok().unwrap();
+		let labels: Vec<_> = members(archive).into_iter().map(|(l, _)| l).collect();
+
+		assert_eq!(
+			labels,
+			[format!("{}:dir/a.txt", path), format!("{}:b.txt", path)]
+		);
+	}
+
+	#[test]
+	fn not_archive() {
+		let stream =
+			Stream::from_member(&b"lorem ipsum"[..], String::from("test")).unwrap();
+
+		assert!(Archive::from_stream(stream).is_err());
+	}
+}
@@ -1,8 +1,21 @@
-use clap::{Parser, ValueEnum};
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+use clap::{ArgGroup, Parser, ValueEnum};
 use regex::Regex;
+use rust_stemmers::Algorithm;
 
-use crate::regexes::{
-	ALL_REGEX, ALPHANUMERIC_REGEX, ALPHA_REGEX, NOAPOSTROPHE_REGEX, NODASH_REGEX, NUMERIC_REGEX,
+use crate::{
+	count::CountOptions,
+	exclusions::Exclusions,
+	normalize::{CaseMode, Normalizer},
+	output::CsvOptions,
+	regexes::{
+		ALL_REGEX, ALPHANUMERIC_REGEX, ALPHA_REGEX, NOAPOSTROPHE_REGEX, NODASH_REGEX,
+		NUMERIC_REGEX,
+	},
+	tokenizer::Tokenizer,
+	walk::WalkOptions,
 };
 
 /// Options for displaying the `total_count` column in the result.
@@ -36,24 +49,228 @@ pub enum WordRegex {
 	OnlyNumeric,
 	NoDash,
 	NoApostrophe,
+	/// Unicode word boundaries (UAX #29), instead of a regular expression.
+	Unicode,
 }
 
 impl WordRegex {
-	/// Returns corresponding Regex.
-	pub fn to_regex(self) -> &'static Regex {
-		match self {
+	/// Returns corresponding Tokenizer.
+	pub fn to_tokenizer(self) -> Tokenizer {
+		let regex: &Regex = match self {
 			WordRegex::All => &ALL_REGEX,
 			WordRegex::OnlyAlphanumeric => &ALPHANUMERIC_REGEX,
 			WordRegex::OnlyAlphabetic => &ALPHA_REGEX,
 			WordRegex::OnlyNumeric => &NUMERIC_REGEX,
 			WordRegex::NoDash => &NODASH_REGEX,
 			WordRegex::NoApostrophe => &NOAPOSTROPHE_REGEX,
+			WordRegex::Unicode => return Tokenizer::Unicode,
+		};
+
+		Tokenizer::Regex(regex.clone(), None)
+	}
+}
+
+/// Represents possible units of text to count.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CountUnit {
+	/// Words, as matched by `--pattern` or `--regex`.
+	Word,
+	/// Grapheme clusters, user perceived characters.
+	Grapheme,
+}
+
+/// Represents the languages that the words can be stemmed in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum StemLanguage {
+	Arabic,
+	Danish,
+	Dutch,
+	English,
+	Finnish,
+	French,
+	German,
+	Greek,
+	Hungarian,
+	Italian,
+	Norwegian,
+	Portuguese,
+	Romanian,
+	Russian,
+	Spanish,
+	Swedish,
+	Tamil,
+	Turkish,
+}
+
+impl StemLanguage {
+	/// Returns the Snowball algorithm of the language.
+	pub fn algorithm(self) -> Algorithm {
+		match self {
+			StemLanguage::Arabic => Algorithm::Arabic,
+			StemLanguage::Danish => Algorithm::Danish,
+			StemLanguage::Dutch => Algorithm::Dutch,
+			StemLanguage::English => Algorithm::English,
+			StemLanguage::Finnish => Algorithm::Finnish,
+			StemLanguage::French => Algorithm::French,
+			StemLanguage::German => Algorithm::German,
+			StemLanguage::Greek => Algorithm::Greek,
+			StemLanguage::Hungarian => Algorithm::Hungarian,
+			StemLanguage::Italian => Algorithm::Italian,
+			StemLanguage::Norwegian => Algorithm::Norwegian,
+			StemLanguage::Portuguese => Algorithm::Portuguese,
+			StemLanguage::Romanian => Algorithm::Romanian,
+			StemLanguage::Russian => Algorithm::Russian,
+			StemLanguage::Spanish => Algorithm::Spanish,
+			StemLanguage::Swedish => Algorithm::Swedish,
+			StemLanguage::Tamil => Algorithm::Tamil,
+			StemLanguage::Turkish => Algorithm::Turkish,
+		}
+	}
+}
+
+/// Represents Unicode normalization forms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum NormalForm {
+	Nfc,
+	Nfkc,
+	Nfd,
+	Nfkd,
+}
+
+/// Represents the locales with special case mapping rules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CaseLocale {
+	/// Turkish.
+	Tr,
+	/// Azerbaijani.
+	Az,
+}
+
+/// Represents the languages that have bundled stop-word lists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum StopwordLanguage {
+	/// German.
+	De,
+	/// English.
+	En,
+	/// Spanish.
+	Es,
+	/// French.
+	Fr,
+	/// Italian.
+	It,
+	/// Dutch.
+	Nl,
+	/// Portuguese.
+	Pt,
+	/// Turkish.
+	Tr,
+}
+
+/// Represents the formats that the results can be written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+	/// Comma seperated values, a column per stream.
+	Csv,
+	/// A single JSON object, with the streams, the total and the rows.
+	Json,
+	/// A JSON object per word, one per line.
+	Ndjson,
+	/// A table with aligned columns, for the terminal.
+	Table,
+	/// A GitHub Markdown pipe table.
+	Markdown,
+	/// Horizontal bars with the counts and percentages, scaled to the terminal width.
+	Histogram,
+}
+
+/// Represents possible encodings of the input streams.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum InputEncoding {
+	/// UTF-8, streams with invalid UTF-8 are skipped.
+	#[value(name = "utf-8", alias = "utf8")]
+	Utf8,
+	/// UTF-8, invalid sequences are replaced with U+FFFD.
+	#[value(name = "utf-8-lossy", alias = "utf8-lossy")]
+	Utf8Lossy,
+	/// Latin-1, decoded as its superset Windows-1252.
+	#[value(name = "latin1", alias = "windows-1252")]
+	Latin1,
+	/// UTF-16 little endian.
+	#[value(name = "utf-16le", alias = "utf16le")]
+	Utf16le,
+	/// UTF-16 big endian.
+	#[value(name = "utf-16be", alias = "utf16be")]
+	Utf16be,
+	/// Guessed from the start of the stream, UTF-16, UTF-8 or Windows-1252.
+	Auto,
+}
+
+/// Range of the number of words in the counted n-grams, both ends inclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NgramRange {
+	pub min: usize,
+	pub max: usize,
+}
+
+impl FromStr for NgramRange {
+	type Err = anyhow::Error;
+
+	/// Parses either a single number `N`, or a range in the form of `MIN..MAX`.
+	fn from_str(s: &str) -> Result<Self> {
+		let (min, max) = match s.split_once("..") {
+			Some((min, max)) => (min.parse()?, max.parse()?),
+			None => {
+				let n = s.parse()?;
+				(n, n)
+			}
+		};
+
+		if min == 0 || min > max {
+			return Err(anyhow!("expected 1 <= MIN <= MAX"));
 		}
+
+		Ok(NgramRange { min, max })
 	}
 }
 
+/// Character that seperates the fields of the CSV output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Delimiter(pub char);
+
+impl FromStr for Delimiter {
+	type Err = anyhow::Error;
+
+	/// Parses a single character, `\t` or `tab` being the tab character.
+	fn from_str(s: &str) -> Result<Self> {
+		let mut chars = s.chars();
+
+		let c = match (s, chars.next(), chars.next()) {
+			("\\t" | "tab", _, _) => '\t',
+			(_, Some(c), None) => c,
+			_ => return Err(anyhow!("expected a single character")),
+		};
+
+		if matches!(c, '"' | '\r' | '\n') {
+			return Err(anyhow!("can't be a quote or a line break"));
+		}
+
+		Ok(Delimiter(c))
+	}
+}
+
+/// Represents the line endings of the CSV output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LineEnding {
+	/// `\n`, as on Unix.
+	Lf,
+	/// `\r\n`, as on Windows and in RFC 4180.
+	Crlf,
+}
+
 #[derive(Debug, Parser)]
 #[clap(version)]
+#[command(group(ArgGroup::new("case").args(["case_insensitive", "case_fold"]).multiple(true)))]
 pub struct Cli {
 	/// Files that the words will be counted from
 	pub files: Vec<String>,
@@ -62,13 +279,100 @@ pub struct Cli {
 	#[arg(short, long, default_value_t = false)]
 	pub case_insensitive: bool,
 
+	/// Full Unicode case folding, like `-c` but also matches `ß` with `ss`
+	#[arg(long, default_value_t = false)]
+	pub case_fold: bool,
+
+	/// Locale of the case mapping for `-c` and `--case-fold`, for the dotted and dotless i
+	#[arg(long, value_enum, value_name = "LOCALE", requires = "case")]
+	pub locale: Option<CaseLocale>,
+
+	/// Remove the diacritics from the words, so `café` matches `cafe`
+	#[arg(long, default_value_t = false)]
+	pub strip_diacritics: bool,
+
+	/// Unicode normalization form to count the words in, so composed and decomposed accents match
+	#[arg(long, value_enum, value_name = "FORM")]
+	pub normalize: Option<NormalForm>,
+
 	/// Pattern to match for words
 	#[arg(long, value_enum ,default_value_t = {WordRegex::All})]
 	pub pattern: WordRegex,
 
-	/// Words to exclude from the counting process, whitespace seperated
+	/// Custom regular expression to match for words, instead of `--pattern`. Patterns with anchors,
+	/// such as `^`, or that can match a whitespace are matched against the whole file at once
+	#[arg(long, value_name = "REGEX", conflicts_with = "pattern")]
+	pub regex: Option<String>,
+
+	/// Name or index of the capture group of `--regex` to count, instead of the whole match
+	#[arg(long, value_name = "GROUP", requires = "regex")]
+	pub regex_group: Option<String>,
+
+	/// Word list to segment Chinese, Japanese, Thai and similar scripts with, one word per line
+	#[arg(long, value_name = "FILE")]
+	pub dictionary: Option<String>,
+
+	/// Tab seperated file of word forms and their lemmas, to count the words as their lemmas
+	#[arg(long, value_name = "FILE")]
+	pub lemmas: Option<String>,
+
+	/// Number of words to count as a single n-gram, either `N` or a range in the form of `MIN..MAX`
+	#[arg(long, default_value = "1", value_name = "N")]
+	pub ngram: NgramRange,
+
+	/// String to join the units of n-grams with [default: " " for words, "" for graphemes]
+	#[arg(long, value_name = "STRING")]
+	pub ngram_joiner: Option<String>,
+
+	/// Unit to count, `grapheme` with `--ngram` counts character n-grams
+	#[arg(long, value_enum, default_value_t = {CountUnit::Word})]
+	pub unit: CountUnit,
+
+	/// Count whitespace graphemes, only with `--unit grapheme`
+	#[arg(long, default_value_t = false)]
+	pub include_whitespace: bool,
+
+	/// Count punctuation and symbol graphemes, only with `--unit grapheme`
+	#[arg(long, default_value_t = false)]
+	pub include_punctuation: bool,
+
+	/// Count the words by their stems, with the Snowball stemmer of the language. Words are stemmed in lower case, with or without `-c`
+	#[arg(long, value_enum, value_name = "LANG")]
+	pub stem: Option<StemLanguage>,
+
+	/// Display the stems as their most frequent form in the text. Can't be used with n-grams
+	#[arg(
+		long,
+		default_value_t = false,
+		requires = "stem",
+		conflicts_with = "lemmas"
+	)]
+	pub surface_forms: bool,
+
+	/// Don't count the n-grams that span the end of a sentence
+	#[arg(long, default_value_t = false)]
+	pub sentence_boundaries: bool,
+
+	/// Encoding of the files and the word lists, byte order marks take precedence in every option
+	#[arg(long, value_enum, default_value_t = {InputEncoding::Utf8})]
+	pub encoding: InputEncoding,
+
+	/// Words to exclude from the counting process, can be given more than once. Whitespace seperated words, `#` comments, and `re:` regular expression and `glob:` glob lines
+	#[arg(long, value_name = "FILE")]
+	pub excluded_words: Vec<String>,
+
+	/// Only count the words in the file, in the format of `--excluded-words`. Listed words are displayed even if they are not found
 	#[arg(long, value_name = "FILE")]
-	pub excluded_words: Option<String>,
+	pub only_words: Option<String>,
+
+	/// Bundled stop-word lists of the languages to exclude, comma seperated. Stop words are
+	/// matched case insensitively, with or without `-c`
+	#[arg(long, value_enum, value_delimiter = ',', value_name = "LANG")]
+	pub stopwords: Vec<StopwordLanguage>,
+
+	/// Print the bundled stop-word list of the language and exit
+	#[arg(long, value_enum, value_name = "LANG")]
+	pub print_stopwords: Option<StopwordLanguage>,
 
 	/// Number of rows of words and their counts to be displayed, unlimited for 0
 	#[arg(long, default_value_t = 50, value_name = "ROW_COUNT")]
@@ -82,6 +386,22 @@ pub struct Cli {
 	#[arg(long, default_value_t = {"total_count".to_string()}, value_name = "COLUMN_LABEL")]
 	pub total_label: String,
 
+	/// Output format of the results
+	#[arg(long, value_enum, default_value_t = {OutputFormat::Csv}, value_name = "FORMAT")]
+	pub format: OutputFormat,
+
+	/// Field delimiter of the CSV output, a single character or `tab`
+	#[arg(long, default_value = ",", value_name = "CHAR")]
+	pub delimiter: Delimiter,
+
+	/// Line ending of the CSV output
+	#[arg(long, value_enum, default_value_t = {LineEnding::Lf})]
+	pub line_ending: LineEnding,
+
+	/// Start the CSV output with a UTF-8 byte order mark, for spreadsheet programs
+	#[arg(long, default_value_t = false)]
+	pub bom: bool,
+
 	/// Show the results in ascending order, instead of descending
 	#[arg(short, long, default_value_t = false)]
 	pub reverse: bool,
@@ -89,6 +409,127 @@ pub struct Cli {
 	/// Close the process at any warning
 	#[arg(short = 'W', long)]
 	pub werror: bool,
+
+	/// Count the files in directories recursively
+	#[arg(short = 'R', long, default_value_t = false)]
+	pub recursive: bool,
+
+	/// Maximum depth of the directory traversal, unlimited if not given
+	#[arg(long, value_name = "DEPTH", requires = "recursive")]
+	pub max_depth: Option<usize>,
+
+	/// Follow symbolic links while traversing directories
+	#[arg(short = 'L', long, default_value_t = false, requires = "recursive")]
+	pub follow_links: bool,
+
+	/// Include hidden files and directories while traversing directories
+	#[arg(long, default_value_t = false, requires = "recursive")]
+	pub hidden: bool,
+
+	/// Count every file in a directory into a single column, instead of a column per file
+	#[arg(long, default_value_t = false, requires = "recursive")]
+	pub aggregate_dirs: bool,
+
+	/// Don't respect `.gitignore` and `.ignore` files while traversing directories
+	#[arg(long, default_value_t = false, requires = "recursive")]
+	pub no_ignore: bool,
+
+	/// Only count the files that match the glob while traversing directories, even the ignored
+	/// ones, can be repeated
+	#[arg(long, value_name = "GLOB", requires = "recursive")]
+	pub include: Vec<String>,
+
+	/// Skip the files that match the glob while traversing directories, can be repeated
+	#[arg(long, value_name = "GLOB", requires = "recursive")]
+	pub exclude: Vec<String>,
+
+	/// Count every file in an archive into a single column, instead of a column per file
+	#[arg(long, default_value_t = false)]
+	pub aggregate_archives: bool,
+
+	/// Number of threads to count with, number of CPU cores if not given
+	#[arg(short, long, value_name = "N")]
+	pub jobs: Option<usize>,
+
+	/// Print the files that would be counted and exit
+	#[arg(long, default_value_t = false)]
+	pub list_files: bool,
+}
+
+impl Cli {
+	/// Returns the `Tokenizer` that is selected by `--regex` or `--pattern`.
+	pub fn tokenizer(&self) -> Result<Tokenizer> {
+		match &self.regex {
+			Some(r) => Tokenizer::from_user_regex(r, self.regex_group.as_deref()),
+			None => Ok(self.pattern.to_tokenizer()),
+		}
+	}
+
+	/// Returns the counting options, with the given `tokenizer`.
+	pub fn count_options(&self, tokenizer: Tokenizer) -> CountOptions {
+		CountOptions {
+			tokenizer,
+			normalizer: self.normalizer(),
+			encoding: self.encoding,
+			ngram: self.ngram,
+			joiner: self
+				.ngram_joiner
+				.clone()
+				.unwrap_or_else(|| match self.unit {
+					CountUnit::Word => String::from(" "),
+					CountUnit::Grapheme => String::new(),
+				}),
+			unit: self.unit,
+			include_whitespace: self.include_whitespace,
+			include_punctuation: self.include_punctuation,
+			lemmas: None,
+			stem: self.stem,
+			surface_forms: self.surface_forms,
+			exclusions: Exclusions::default(),
+			only_words: None,
+			sentence_boundaries: self.sentence_boundaries,
+		}
+	}
+
+	/// Returns the `Normalizer` for the counted text and the word lists.
+	pub fn normalizer(&self) -> Normalizer {
+		let case = if self.case_fold {
+			CaseMode::Fold
+		} else if self.case_insensitive {
+			CaseMode::Lower
+		} else {
+			CaseMode::Preserve
+		};
+
+		Normalizer {
+			case,
+			locale: self.locale,
+			strip_diacritics: self.strip_diacritics,
+			form: self.normalize,
+		}
+	}
+
+	/// Returns the options of the CSV output.
+	pub fn csv_options(&self) -> CsvOptions {
+		CsvOptions {
+			delimiter: self.delimiter.0,
+			line_ending: self.line_ending,
+			bom: self.bom,
+		}
+	}
+
+	/// Returns the directory traversal options, `None` if `--recursive` is not set.
+	pub fn walk_options(&self) -> Option<WalkOptions> {
+		self.recursive.then(|| WalkOptions {
+			max_depth: self.max_depth,
+			follow_links: self.follow_links,
+			hidden: self.hidden,
+			aggregate: self.aggregate_dirs,
+			respect_ignore: !self.no_ignore,
+			include: self.include.clone(),
+			exclude: self.exclude.clone(),
+		})
+	}
 }
 
 #[cfg(test)]
@@ -306,4 +747,423 @@ mod tests {
 		assert!(!cli.case_insensitive);
 		assert!(cli.reverse);
 	}
+
+	#[test]
+	fn encoding() {
+		let cmd = Cli::command();
+		let matches = cmd.get_matches_from(vec![
+			"wcount", // executable name
+			"file1.txt",
+		]);
+
+		let cli = Cli::from_arg_matches(&matches).unwrap();
+
+		assert!(matches!(cli.encoding, InputEncoding::Utf8));
+
+		let cmd = Cli::command();
+		let matches = cmd.get_matches_from(vec![
+			"wcount", // executable name
+			"file1.txt",
+			"--encoding",
+			"windows-1252",
+		]);
+
+		let cli = Cli::from_arg_matches(&matches).unwrap();
+
+		assert!(matches!(cli.encoding, InputEncoding::Latin1));
+	}
+
+	#[test]
+	fn regex() {
+		let cmd = Cli::command();
+		let matches = cmd.get_matches_from(vec![
+			"wcount", // executable name
+			"file1.txt",
+			"--regex",
+			r"#(?<tag>\w+)",
+			"--regex-group",
+			"tag",
+		]);
+
+		let cli = Cli::from_arg_matches(&matches).unwrap();
+
+		assert_eq!(cli.regex.as_deref(), Some(r"#(?<tag>\w+)"));
+		assert_eq!(cli.regex_group.as_deref(), Some("tag"));
+		assert!(matches!(
+			cli.tokenizer().unwrap(),
+			Tokenizer::Regex(_, Some(1))
+		));
+	}
+
+	#[test]
+	fn unicode_pattern() {
+		let cmd = Cli::command();
+		let matches = cmd.get_matches_from(vec![
+			"wcount", // executable name
+			"file1.txt",
+			"--pattern",
+			"unicode",
+		]);
+
+		let cli = Cli::from_arg_matches(&matches).unwrap();
+
+		assert!(matches!(cli.pattern, WordRegex::Unicode));
+		assert!(matches!(cli.tokenizer().unwrap(), Tokenizer::Unicode));
+	}
+
+	#[test]
+	fn regex_conflicts_pattern() {
+		let cmd = Cli::command();
+		let res = cmd.try_get_matches_from(vec![
+			"wcount", // executable name
+			"file1.txt",
+			"--regex",
+			r"\w+",
+			"--pattern",
+			"all",
+		]);
+
+		assert!(res.is_err());
+	}
+
+	#[test]
+	fn ngram1() {
+		let cmd = Cli::command();
+		let matches = cmd.get_matches_from(vec![
+			"wcount", // executable name
+			"file1.txt",
+		]);
+
+		let cli = Cli::from_arg_matches(&matches).unwrap();
+
+		assert_eq!(cli.ngram, NgramRange { min: 1, max: 1 });
+
+		let cmd = Cli::command();
+		let matches = cmd.get_matches_from(vec![
+			"wcount", // executable name
+			"file1.txt",
+			"--ngram",
+			"2",
+		]);
+
+		let cli = Cli::from_arg_matches(&matches).unwrap();
+
+		assert_eq!(cli.ngram, NgramRange { min: 2, max: 2 });
+		assert!(cli.ngram_joiner.is_none());
+		assert_eq!(cli.count_options(WordRegex::All.to_tokenizer()).joiner, " ");
+		assert!(!cli.sentence_boundaries);
+	}
+
+	#[test]
+	fn ngram2() {
+		let cmd = Cli::command();
+		let matches = cmd.get_matches_from(vec![
+			"wcount", // executable name
+			"file1.txt",
+			"--ngram",
+			"1..3",
+			"--ngram-joiner",
+			"_",
+			"--sentence-boundaries",
+		]);
+
+		let cli = Cli::from_arg_matches(&matches).unwrap();
+
+		assert_eq!(cli.ngram, NgramRange { min: 1, max: 3 });
+		assert_eq!(cli.ngram_joiner.as_deref(), Some("_"));
+		assert!(cli.sentence_boundaries);
+	}
+
+	#[test]
+	fn grapheme() {
+		let cmd = Cli::command();
+		let matches = cmd.get_matches_from(vec![
+			"wcount", // executable name
+			"file1.txt",
+			"--unit",
+			"grapheme",
+			"--ngram",
+			"2",
+			"--include-whitespace",
+		]);
+
+		let cli = Cli::from_arg_matches(&matches).unwrap();
+		let opts = cli.count_options(WordRegex::All.to_tokenizer());
+
+		assert_eq!(opts.unit, CountUnit::Grapheme);
+		assert_eq!(opts.joiner, "");
+		assert!(opts.include_whitespace);
+		assert!(!opts.include_punctuation);
+	}
+
+	#[test]
+	fn stem() {
+		let cmd = Cli::command();
+		let matches = cmd.get_matches_from(vec![
+			"wcount", // executable name
+			"file1.txt",
+			"--stem",
+			"english",
+			"--surface-forms",
+		]);
+
+		let cli = Cli::from_arg_matches(&matches).unwrap();
+
+		assert_eq!(cli.stem, Some(StemLanguage::English));
+		assert!(cli.surface_forms);
+	}
+
+	#[test]
+	fn surface_forms_requires_stem() {
+		let cmd = Cli::command();
+		let res = cmd.try_get_matches_from(vec![
+			"wcount", // executable name
+			"file1.txt",
+			"--surface-forms",
+		]);
+
+		assert!(res.is_err());
+	}
+
+	#[test]
+	fn locale_requires_case() {
+		let cmd = Cli::command();
+		let res = cmd.try_get_matches_from(vec![
+			"wcount", // executable name
+			"file1.txt",
+			"--locale",
+			"tr",
+		]);
+
+		assert!(res.is_err());
+
+		for case in ["-c", "--case-fold"] {
+			let cmd = Cli::command();
+			let res = cmd.try_get_matches_from(vec![
+				"wcount", // executable name
+				"file1.txt",
+				case,
+				"--locale",
+				"tr",
+			]);
+
+			assert!(res.is_ok());
+		}
+	}
+
+	#[test]
+	fn normalize() {
+		let cmd = Cli::command();
+		let matches = cmd.get_matches_from(vec![
+			"wcount", // executable name
+			"file1.txt",
+			"-c",
+			"--case-fold",
+			"--locale",
+			"tr",
+			"--normalize",
+			"nfkc",
+			"--strip-diacritics",
+		]);
+
+		let cli = Cli::from_arg_matches(&matches).unwrap();
+		let normalizer = cli.normalizer();
+
+		assert_eq!(normalizer.case, CaseMode::Fold);
+		assert_eq!(normalizer.locale, Some(CaseLocale::Tr));
+		assert!(normalizer.strip_diacritics);
+		assert_eq!(normalizer.form, Some(NormalForm::Nfkc));
+	}
+
+	#[test]
+	fn stopwords() {
+		let cmd = Cli::command();
+		let matches = cmd.get_matches_from(vec![
+			"wcount", // executable name
+			"file1.txt",
+			"--stopwords",
+			"en,de",
+			"--stopwords",
+			"tr",
+		]);
+
+		let cli = Cli::from_arg_matches(&matches).unwrap();
+
+		assert_eq!(
+			cli.stopwords,
+			vec![
+				StopwordLanguage::En,
+				StopwordLanguage::De,
+				StopwordLanguage::Tr
+			]
+		);
+		assert_eq!(cli.print_stopwords, None);
+	}
+
+	#[test]
+	fn format() {
+		let cmd = Cli::command();
+		let matches = cmd.get_matches_from(vec![
+			"wcount", // executable name
+			"file1.txt",
+		]);
+
+		let cli = Cli::from_arg_matches(&matches).unwrap();
+
+		assert_eq!(cli.format, OutputFormat::Csv);
+
+		let cmd = Cli::command();
+		let matches = cmd.get_matches_from(vec![
+			"wcount", // executable name
+			"file1.txt",
+			"--format",
+			"markdown",
+		]);
+
+		let cli = Cli::from_arg_matches(&matches).unwrap();
+
+		assert_eq!(cli.format, OutputFormat::Markdown);
+	}
+
+	#[test]
+	fn csv_options() {
+		let cmd = Cli::command();
+		let matches = cmd.get_matches_from(vec![
+			"wcount", // executable name
+			"file1.txt",
+			"--delimiter",
+			"tab",
+			"--line-ending",
+			"crlf",
+			"--bom",
+		]);
+
+		let cli = Cli::from_arg_matches(&matches).unwrap();
+		let opts = cli.csv_options();
+
+		assert_eq!(opts.delimiter, '\t');
+		assert_eq!(opts.line_ending, LineEnding::Crlf);
+		assert!(opts.bom);
+	}
+
+	#[test]
+	fn delimiter() {
+		assert_eq!(Delimiter::from_str(";").unwrap(), Delimiter(';'));
+		assert_eq!(Delimiter::from_str("\\t").unwrap(), Delimiter('\t'));
+
+		for d in ["", ";;", "\"", "\n"] {
+			assert!(Delimiter::from_str(d).is_err());
+		}
+	}
+
+	#[test]
+	fn ngram_invalid() {
+		for n in ["0", "3..1", "a", "1..", "0..2"] {
+			let cmd = Cli::command();
+			let res = cmd.try_get_matches_from(vec![
+				"wcount", // executable name
+				"file1.txt",
+				"--ngram",
+				n,
+			]);
+
+			assert!(res.is_err());
+		}
+	}
+
+	#[test]
+	fn jobs() {
+		let cmd = Cli::command();
+		let matches = cmd.get_matches_from(vec![
+			"wcount", // executable name
+			"file1.txt",
+			"file2.txt",
+			"-j",
+			"4",
+		]);
+
+		let cli = Cli::from_arg_matches(&matches).unwrap();
+
+		assert_eq!(cli.files, vec!["file1.txt", "file2.txt"]);
+		assert_eq!(cli.jobs, Some(4));
+	}
+
+	#[test]
+	fn recursive() {
+		let cmd = Cli::command();
+		let matches = cmd.get_matches_from(vec![
+			"wcount", // executable name
+			"dir1",
+			"file2.txt",
+			"-R",
+			"--max-depth",
+			"3",
+			"--hidden",
+		]);
+
+		let cli = Cli::from_arg_matches(&matches).unwrap();
+		let walk = cli.walk_options().unwrap();
+
+		assert_eq!(cli.files, vec!["dir1", "file2.txt"]);
+		assert!(cli.recursive);
+		assert_eq!(walk.max_depth, Some(3));
+		assert!(!walk.follow_links);
+		assert!(walk.hidden);
+		assert!(!walk.aggregate);
+		assert!(walk.respect_ignore);
+		assert!(walk.include.is_empty());
+		assert!(walk.exclude.is_empty());
+	}
+
+	#[test]
+	fn include_exclude() {
+		let cmd = Cli::command();
+		let matches = cmd.get_matches_from(vec![
+			"wcount", // executable name
+			"dir1",
+			"-R",
+			"--include",
+			"**/*.md",
+			"--include",
+			"*.txt",
+			"--exclude",
+			"target/",
+			"--no-ignore",
+		]);
+
+		let cli = Cli::from_arg_matches(&matches).unwrap();
+		let walk = cli.walk_options().unwrap();
+
+		assert!(!walk.respect_ignore);
+		assert_eq!(walk.include, vec!["**/*.md", "*.txt"]);
+		assert_eq!(walk.exclude, vec!["target/"]);
+		assert!(!cli.list_files);
+	}
+
+	#[test]
+	fn not_recursive() {
+		let cmd = Cli::command();
+		let matches = cmd.get_matches_from(vec![
+			"wcount", // executable name
+			"file1.txt",
+			"file2.txt",
+		]);
+
+		let cli = Cli::from_arg_matches(&matches).unwrap();
+
+		assert!(!cli.recursive);
+		assert!(cli.walk_options().is_none());
+	}
+
+	#[test]
+	fn walk_requires_recursive() {
+		let cmd = Cli::command();
+		let res = cmd.try_get_matches_from(vec![
+			"wcount", // executable name
+			"dir1",
+			"--aggregate-dirs",
+		]);
+
+		assert!(res.is_err());
+	}
 }
@@ -1,77 +1,469 @@
 use std::{
-	cell::RefCell,
-	collections::HashMap,
+	borrow::Cow,
+	collections::{HashMap, VecDeque},
 	hash::{BuildHasher, Hash},
+	sync::atomic::{AtomicUsize, Ordering},
 };
 
-use regex::Regex;
+use rayon::prelude::*;
+use unicode_segmentation::UnicodeSegmentation;
 use ustr::{ustr, Ustr, UstrMap};
 
-use crate::stream::Stream;
+use rust_stemmers::Stemmer;
+
+use crate::{
+	args::{CountUnit, InputEncoding, NgramRange, StemLanguage, WordRegex},
+	exclusions::Exclusions,
+	lemmas::Lemmas,
+	normalize::Normalizer,
+	regexes::PUNCTUATION_REGEX,
+	stream::Stream,
+	tokenizer::Tokenizer,
+};
+
+/// Minimum size of the text that is counted by a single thread.
+///
+/// Streams are counted in batches, see `batch_size`, and each batch is split into pieces that are
+/// counted in parallel.
+const PIECE_SIZE: usize = 1024 * 1024;
+
+/// Number of streams that are being counted at the moment.
+static ACTIVE_STREAMS: AtomicUsize = AtomicUsize::new(0);
+
+/// Characters that end a sentence, n-grams don't span them if `sentence_boundaries` is set.
+const SENTENCE_ENDS: &[char] = &['.', '!', '?', '…', '。', '！', '？'];
+
+/// Options that control how the streams are counted.
+#[derive(Debug, Clone)]
+pub struct CountOptions {
+	/// Splits the text into words.
+	pub tokenizer: Tokenizer,
+	/// Maps the case and the normalization form of the text.
+	pub normalizer: Normalizer,
+	/// Encoding of the streams.
+	pub encoding: InputEncoding,
+	/// Number of words in the n-grams that are counted.
+	pub ngram: NgramRange,
+	/// String that the words of an n-gram are joined with.
+	pub joiner: String,
+	/// Don't count the n-grams that span sentence boundaries.
+	pub sentence_boundaries: bool,
+	/// Unit of text to count.
+	pub unit: CountUnit,
+	/// Count whitespace graphemes.
+	pub include_whitespace: bool,
+	/// Count punctuation and symbol graphemes.
+	pub include_punctuation: bool,
+	/// Maps the words to their lemmas.
+	pub lemmas: Option<Lemmas>,
+	/// Language to stem the words with.
+	pub stem: Option<StemLanguage>,
+	/// Keep the counts of the words before stemming, see `StreamWordCount::forms`.
+	pub surface_forms: bool,
+	/// Words that are not counted.
+	pub exclusions: Exclusions,
+	/// Only count the words in the list, if set.
+	pub only_words: Option<Exclusions>,
+}
+
+impl Default for CountOptions {
+	fn default() -> Self {
+		CountOptions {
+			tokenizer: WordRegex::All.to_tokenizer(),
+			normalizer: Normalizer::default(),
+			encoding: InputEncoding::Utf8,
+			ngram: NgramRange { min: 1, max: 1 },
+			joiner: String::from(" "),
+			sentence_boundaries: false,
+			unit: CountUnit::Word,
+			include_whitespace: false,
+			include_punctuation: false,
+			lemmas: None,
+			stem: None,
+			surface_forms: false,
+			exclusions: Exclusions::default(),
+			only_words: None,
+		}
+	}
+}
+
+impl CountOptions {
+	/// Checks if the word, or n-gram, `s` is counted.
+	#[inline]
+	fn is_counted(&self, s: &Ustr) -> bool {
+		!self.exclusions.contains(s)
+			&& self.only_words.as_ref().is_none_or(|o| o.contains(s))
+	}
+
+	/// Increments the count of `s` in `counts`, unless it is excluded.
+	#[inline]
+	fn add_count(&self, counts: &mut UstrMap<usize>, s: Ustr) {
+		// Words that are already in `counts` are known to be counted, so the exclusion rules are
+		// only checked once for them.
+		match counts.get_mut(&s) {
+			Some(c) => *c += 1,
+			None if self.is_counted(&s) => {
+				counts.insert(s, 1);
+			}
+			None => {}
+		}
+	}
+}
+
+/// Last words of a stream, that the following n-grams are formed with.
+///
+/// Carried between the chunks of the stream, so n-grams span them.
+#[derive(Default)]
+struct NgramWindow {
+	words: VecDeque<Ustr>,
+	/// Buffer that the n-grams are joined into.
+	buf: String,
+}
+
+impl NgramWindow {
+	/// Adds `word` to the window, and counts every n-gram that ends with it.
+	fn push(&mut self, counts: &mut UstrMap<usize>, word: Ustr, opts: &CountOptions) {
+		if self.words.len() == opts.ngram.max {
+			self.words.pop_front();
+		}
+		self.words.push_back(word);
+
+		for n in opts.ngram.min..=opts.ngram.max.min(self.words.len()) {
+			self.buf.clear();
+
+			for (i, w) in self.words.range(self.words.len() - n..).enumerate() {
+				if i > 0 {
+					self.buf.push_str(&opts.joiner);
+				}
+				self.buf.push_str(w);
+			}
+
+			opts.add_count(counts, ustr(&self.buf));
+		}
+	}
+
+	/// Removes every word from the window.
+	#[inline]
+	fn clear(&mut self) {
+		self.words.clear();
+	}
+}
+
+/// Marks a stream as being counted in `ACTIVE_STREAMS`, until it is dropped.
+struct ActiveStream;
+
+impl ActiveStream {
+	fn start() -> Self {
+		ACTIVE_STREAMS.fetch_add(1, Ordering::Relaxed);
+		ActiveStream
+	}
+}
+
+impl Drop for ActiveStream {
+	fn drop(&mut self) {
+		ACTIVE_STREAMS.fetch_sub(1, Ordering::Relaxed);
+	}
+}
+
+/// Returns the size of the batches that a stream is counted in, when `streams` streams are
+/// counted on `threads` threads.
+///
+/// Every stream gets an equal share of the threads, and a piece of the batch for each of them. So
+/// a single large stream is counted on every thread, and the batches of all the streams add up
+/// to about `PIECE_SIZE` times the number of threads.
+#[inline]
+fn batch_size(threads: usize, streams: usize) -> usize {
+	PIECE_SIZE * (threads / streams.max(1)).max(1)
+}
+
+/// Returns `word` in lower case, for the stemmers.
+///
+/// Snowball stemmers only work on lower case words, so the words are lower cased for them even
+/// if the counted words are not case mapped. The forms keep their case.
+#[inline]
+fn stem_case(word: &str) -> Cow<'_, str> {
+	if word.chars().any(char::is_uppercase) {
+		Cow::Owned(word.to_lowercase())
+	} else {
+		Cow::Borrowed(word)
+	}
+}
 
 /// Counts of every word in a stream.
 pub struct StreamWordCount {
-	/// Stream to count from.
-	pub from: Stream,
+	/// Label of the stream, or streams, that the words are counted from.
+	pub label: String,
 	/// Maps word to count.
 	///
 	/// UstrMap is used instead of the regular HashMap to avoid String duplication in the memory and the custom Hasher that avoids access hashing.
 	pub counts: UstrMap<usize>,
+	/// Maps word to count, before the words are stemmed.
+	///
+	/// Only kept if `surface_forms` is set, to display the stems as their most frequent form.
+	pub forms: UstrMap<usize>,
+	/// Number of the words that had no lemma, if `lemmas` is set.
+	pub unlemmatized: usize,
 }
 
 impl StreamWordCount {
+	/// Creates an empty StreamWordCount with the given label.
+	pub fn new(label: String) -> Self {
+		StreamWordCount {
+			label,
+			counts: UstrMap::default(),
+			forms: UstrMap::default(),
+			unlemmatized: 0,
+		}
+	}
+
 	/// Creates StreamWordCount from a `Stream`.
-	pub fn from_stream(
-		mut stream: Stream,
-		pattern: &'static Regex,
-		case_insensitive: bool,
-	) -> Option<Self> {
-		thread_local! {
-			static BUF: RefCell<String> = const {RefCell::new(String::new())};
-		}
-
-		// TODO: Better error handling
-		BUF.with_borrow_mut(|buf| {
-			buf.clear();
-			stream.read_to_string(buf)?;
-
-			Some(StreamWordCount {
-				from: stream,
-				counts: Self::count_words(buf, pattern, case_insensitive),
-			})
-		})
+	///
+	/// The stream is read and counted in batches, so the whole stream is never in the memory.
+	/// Large batches are split and counted in parallel, unless n-grams are counted.
+	///
+	/// If the tokenizer can't split the text, see `Tokenizer::is_splittable`, the whole stream is
+	/// read and counted at once instead.
+	pub fn from_stream(mut stream: Stream, opts: &CountOptions) -> Option<Self> {
+		let _active = ActiveStream::start();
+		let mut res = StreamWordCount::new(stream.label());
+		let mut window = NgramWindow::default();
+		let mut batch = String::new();
+
+		if !opts.tokenizer.is_splittable() {
+			stream.read_chunks(opts.encoding, |chunk| batch.push_str(chunk))?;
+			res.count_words(&batch, opts, &mut window);
+
+			return Some(res);
+		}
+
+		stream.read_chunks(opts.encoding, |chunk| {
+			batch.push_str(chunk);
+
+			let size = batch_size(
+				rayon::current_num_threads(),
+				ACTIVE_STREAMS.load(Ordering::Relaxed),
+			);
+
+			// Chunks that are split in the middle of a word are counted right away, so the
+			// word is not joined with the next chunk.
+			if batch.len() >= size || !chunk.ends_with(char::is_whitespace) {
+				res.count_batch(&batch, opts, &mut window);
+				batch.clear();
+			}
+		})?;
+		res.count_batch(&batch, opts, &mut window);
+
+		Some(res)
 	}
 
 	/// Returns word to count pairs as a sorted Vec.
 	#[allow(unused)]
 	pub fn to_ordered_vec(&self) -> Vec<(Ustr, usize)> {
 		let mut res: Vec<_> = self.counts.iter().map(|(s, i)| (*s, *i)).collect();
-		res.sort_by(|(_, a), (_, b)| a.cmp(b));
+		res.sort_by_key(|(_, a)| *a);
 
 		res
 	}
 
-	/// Returns the label of the `Stream`.
+	/// Adds the counts of `other` to this one.
+	pub fn merge(&mut self, other: &StreamWordCount) {
+		TotalCount::merge_maps(&mut self.counts, &other.counts);
+		TotalCount::merge_maps(&mut self.forms, &other.forms);
+		self.unlemmatized += other.unlemmatized;
+	}
+
+	/// Returns the label of the counted stream.
 	#[inline]
-	pub fn label(&self) -> String {
-		self.from.label()
+	pub fn label(&self) -> &str {
+		&self.label
 	}
 
-	/// Counts every string slice, that is recognised as a word by the `pattern`, and returns the counts as a UstrMap.
-	fn count_words(s: &str, pattern: &'static Regex, case_insensitive: bool) -> UstrMap<usize> {
-		let text = if case_insensitive {
-			&s.to_lowercase()
-		} else {
-			s
+	/// Splits `s` into pieces of `PIECE_SIZE`, counts them in parallel and adds the counts to
+	/// this one.
+	///
+	/// N-grams span the pieces, so they are counted sequentially.
+	fn count_batch(&mut self, s: &str, opts: &CountOptions, window: &mut NgramWindow) {
+		let pieces = Self::split_pieces(s, PIECE_SIZE);
+
+		if pieces.len() < 2 || opts.ngram.max > 1 {
+			self.count_words(s, opts, window);
+			return;
+		}
+
+		let piece_counts: Vec<_> = pieces
+			.par_iter()
+			.map(|p| {
+				let mut piece_counts = StreamWordCount::new(String::new());
+				piece_counts.count_words(p, opts, &mut NgramWindow::default());
+				piece_counts
+			})
+			.collect();
+
+		for c in piece_counts.iter() {
+			self.merge(c);
+		}
+	}
+
+	/// Splits `s` into pieces of at least `size` bytes, after a whitespace so words are not split.
+	fn split_pieces(s: &str, size: usize) -> Vec<&str> {
+		let mut pieces = Vec::new();
+		let mut rest = s;
+
+		while rest.len() > size {
+			let mut target = size;
+			while !rest.is_char_boundary(target) {
+				target += 1;
+			}
+
+			let Some(i) = rest[target..].find(char::is_whitespace) else {
+				break;
+			};
+			let end = target
+				+ i + rest[target + i..].chars().next().map_or(0, char::len_utf8);
+
+			let (piece, r) = rest.split_at(end);
+			pieces.push(piece);
+			rest = r;
+		}
+
+		if !rest.is_empty() {
+			pieces.push(rest);
+		}
+
+		pieces
+	}
+
+	/// Counts every string slice, that is recognised as a word by the tokenizer, and adds the counts to this one.
+	///
+	/// If n-grams are counted, `window` holds the last words of the previous text.
+	fn count_words(&mut self, s: &str, opts: &CountOptions, window: &mut NgramWindow) {
+		let text = opts.normalizer.normalize(s);
+		let text = text.as_ref();
+
+		match opts.unit {
+			CountUnit::Word => {
+				let stemmer = opts.stem.map(|l| Stemmer::create(l.algorithm()));
+				// End of the last word, to find the text between the words.
+				let mut last_end = 0;
+				let is_boundary = |gap: Option<&str>| {
+					opts.ngram.max > 1
+						&& opts.sentence_boundaries && gap
+						.is_some_and(|g| g.contains(SENTENCE_ENDS))
+				};
+
+				opts.tokenizer.for_each_token(text, |token| {
+					// Tokens are always slices of `text`.
+					let start =
+						token.as_ptr() as usize - text.as_ptr() as usize;
+
+					if is_boundary(text.get(last_end..start)) {
+						window.clear();
+					}
+					last_end = start + token.len();
+
+					self.count_word(token, opts, stemmer.as_ref(), window);
+				});
+
+				if is_boundary(text.get(last_end..)) {
+					window.clear();
+				}
+			}
+			CountUnit::Grapheme => {
+				for g in text.graphemes(true) {
+					// Skipped graphemes break the n-grams, so they don't span words.
+					if Self::is_skipped_grapheme(g, opts) {
+						window.clear();
+					} else {
+						self.count_unit(g, opts, window);
+					}
+				}
+			}
+		}
+	}
+
+	/// Counts `word` as its lemma and stem, if they are enabled.
+	#[inline]
+	fn count_word(
+		&mut self,
+		word: &str,
+		opts: &CountOptions,
+		stemmer: Option<&Stemmer>,
+		window: &mut NgramWindow,
+	) {
+		if opts.surface_forms {
+			*self.forms.entry(ustr(word)).or_insert(0) += 1;
+		}
+
+		let word = match &opts.lemmas {
+			Some(lemmas) => lemmas.get(word).unwrap_or_else(|| {
+				self.unlemmatized += 1;
+				word
+			}),
+			None => word,
 		};
 
-		let tokens = pattern.find_iter(text).map(|m| m.as_str());
-		let counts = tokens.fold(UstrMap::default(), |mut a, c| {
-			*a.entry(ustr(c)).or_insert(0) += 1;
-			a
-		});
+		match stemmer {
+			Some(stemmer) => {
+				self.count_unit(&stemmer.stem(&stem_case(word)), opts, window)
+			}
+			None => self.count_unit(word, opts, window),
+		}
+	}
 
-		counts
+	/// Counts `unit` by itself, or as the last element of the n-grams in `window`.
+	#[inline]
+	fn count_unit(&mut self, unit: &str, opts: &CountOptions, window: &mut NgramWindow) {
+		if opts.ngram.max == 1 {
+			opts.add_count(&mut self.counts, ustr(unit));
+		} else {
+			window.push(&mut self.counts, ustr(unit), opts);
+		}
+	}
+
+	/// Checks if the grapheme `g` is whitespace or punctuation, and they are not counted.
+	#[inline]
+	fn is_skipped_grapheme(g: &str, opts: &CountOptions) -> bool {
+		if g.starts_with(char::is_whitespace) {
+			!opts.include_whitespace
+		} else {
+			!opts.include_punctuation && PUNCTUATION_REGEX.is_match(g)
+		}
+	}
+
+	/// Replaces the stems in `counts` with their most frequent forms in all of them.
+	///
+	/// Every form has a single stem, so no two stems are replaced with the same form.
+	pub fn use_surface_forms(counts: &mut [StreamWordCount], stem: StemLanguage) {
+		let stemmer = Stemmer::create(stem.algorithm());
+		let mut forms = UstrMap::default();
+
+		for c in counts.iter() {
+			TotalCount::merge_maps(&mut forms, &c.forms);
+		}
+
+		// Maps stem to its most frequent form. Ties are broken by picking the alphabetically
+		// first form, so the result doesn't depend on the order of the map.
+		let mut labels: UstrMap<(Ustr, usize)> = UstrMap::default();
+		for (form, count) in forms {
+			let stem = ustr(&stemmer.stem(&stem_case(&form)));
+
+			labels.entry(stem)
+				.and_modify(|(f, c)| {
+					if count > *c || (count == *c && form.as_str() < f.as_str())
+					{
+						*f = form;
+						*c = count;
+					}
+				})
+				.or_insert((form, count));
+		}
+
+		for c in counts.iter_mut() {
+			c.counts =
+				c.counts.iter()
+					.map(|(s, i)| (labels.get(s).map_or(*s, |(f, _)| *f), *i))
+					.collect();
+		}
 	}
 
 	/// Returns the count of the word `s`.
@@ -111,7 +503,10 @@ impl TotalCount {
 	/// Returns word to count pairs as a sorted Vec.
 	pub fn to_ordered_vec(&self) -> Vec<(Ustr, usize)> {
 		let mut res: Vec<_> = self.counts.iter().map(|(s, i)| (*s, *i)).collect();
-		res.sort_by(|(_, a), (_, b)| a.cmp(b).reverse());
+		// Words with the same count are sorted alphabetically, so the order is stable.
+		res.sort_by(|(a_s, a), (b_s, b)| {
+			b.cmp(a).then_with(|| a_s.as_str().cmp(b_s.as_str()))
+		});
 
 		res
 	}
@@ -129,13 +524,46 @@ impl TotalCount {
 
 #[cfg(test)]
 mod tests {
-	use crate::regexes::ALL_REGEX;
-
 	use super::*;
+	use crate::{args::NormalForm, normalize::CaseMode};
+
+	fn count_words(s: &str, case_insensitive: bool) -> UstrMap<usize> {
+		let opts = CountOptions {
+			normalizer: Normalizer {
+				case: match case_insensitive {
+					true => CaseMode::Lower,
+					false => CaseMode::Preserve,
+				},
+				..Default::default()
+			},
+			..Default::default()
+		};
+		let mut res = StreamWordCount::new(String::new());
+		res.count_words(s, &opts, &mut NgramWindow::default());
+
+		res.counts
+	}
+
+	fn count_ngrams(
+		s: &str,
+		min: usize,
+		max: usize,
+		sentence_boundaries: bool,
+	) -> UstrMap<usize> {
+		let opts = CountOptions {
+			ngram: NgramRange { min, max },
+			sentence_boundaries,
+			..Default::default()
+		};
+		let mut res = StreamWordCount::new(String::new());
+		res.count_words(s, &opts, &mut NgramWindow::default());
+
+		res.counts
+	}
 
 	#[test]
 	fn word_count1() {
-		let res = StreamWordCount::count_words("lorem ipsum dolor", &ALL_REGEX, false);
+		let res = count_words("lorem ipsum dolor", false);
 
 		assert_eq!(res[&ustr("lorem")], 1);
 		assert_eq!(res[&ustr("ipsum")], 1);
@@ -144,11 +572,7 @@ mod tests {
 
 	#[test]
 	fn word_count2() {
-		let res = StreamWordCount::count_words(
-			"lorem dolor ipsum dolor. lorem? dolor dolor",
-			&ALL_REGEX,
-			false,
-		);
+		let res = count_words("lorem dolor ipsum dolor. lorem? dolor dolor", false);
 
 		assert_eq!(res[&ustr("lorem")], 2);
 		assert_eq!(res[&ustr("ipsum")], 1);
@@ -157,11 +581,7 @@ mod tests {
 
 	#[test]
 	fn word_count3() {
-		let res = StreamWordCount::count_words(
-			"Lorem dolor Ipsum dolor. lorem? Dolor dolor",
-			&ALL_REGEX,
-			true,
-		);
+		let res = count_words("Lorem dolor Ipsum dolor. lorem? Dolor dolor", true);
 
 		println!(
 			"{}",
@@ -178,11 +598,7 @@ mod tests {
 
 	#[test]
 	fn word_count4() {
-		let res = StreamWordCount::count_words(
-			"Lorem dolor Ipsum dolor. lorem? Dolor dolor",
-			&ALL_REGEX,
-			false,
-		);
+		let res = count_words("Lorem dolor Ipsum dolor. lorem? Dolor dolor", false);
 
 		assert_eq!(res[&ustr("lorem")], 1);
 		assert_eq!(res[&ustr("Lorem")], 1);
@@ -191,6 +607,375 @@ mod tests {
 		assert_eq!(res[&ustr("Dolor")], 1);
 	}
 
+	#[test]
+	fn ngram1() {
+		let res = count_ngrams("in order to count in order to", 2, 2, false);
+
+		assert_eq!(res[&ustr("in order")], 2);
+		assert_eq!(res[&ustr("order to")], 2);
+		assert_eq!(res[&ustr("to count")], 1);
+		assert_eq!(res[&ustr("count in")], 1);
+		assert_eq!(res.len(), 4);
+	}
+
+	#[test]
+	fn ngram2() {
+		let res = count_ngrams("lorem ipsum dolor", 1, 3, false);
+
+		assert_eq!(res[&ustr("lorem")], 1);
+		assert_eq!(res[&ustr("lorem ipsum")], 1);
+		assert_eq!(res[&ustr("lorem ipsum dolor")], 1);
+		assert_eq!(res[&ustr("ipsum dolor")], 1);
+		assert_eq!(res.len(), 6);
+	}
+
+	#[test]
+	fn ngram3() {
+		let res = count_ngrams("lorem ipsum. Dolor sit! amet", 2, 2, true);
+
+		assert_eq!(res[&ustr("lorem ipsum")], 1);
+		assert_eq!(res[&ustr("Dolor sit")], 1);
+		assert_eq!(res.get(&ustr("ipsum Dolor")), None);
+		assert_eq!(res.len(), 2);
+	}
+
+	#[test]
+	fn ngram4() {
+		let opts = CountOptions {
+			ngram: NgramRange { min: 2, max: 2 },
+			joiner: String::from("_"),
+			..Default::default()
+		};
+		let mut swc = StreamWordCount::new(String::new());
+		let mut window = NgramWindow::default();
+		swc.count_words("lorem ipsum ", &opts, &mut window);
+		swc.count_words("dolor", &opts, &mut window);
+		let res = swc.counts;
+
+		assert_eq!(res[&ustr("lorem_ipsum")], 1);
+		assert_eq!(res[&ustr("ipsum_dolor")], 1);
+		assert_eq!(res.len(), 2);
+	}
+
+	#[test]
+	fn grapheme1() {
+		let opts = CountOptions {
+			unit: CountUnit::Grapheme,
+			..Default::default()
+		};
+		let mut swc = StreamWordCount::new(String::new());
+		swc.count_words("ömür, e\u{301}!", &opts, &mut NgramWindow::default());
+		let res = swc.counts;
+
+		assert_eq!(res[&ustr("ö")], 1);
+		assert_eq!(res[&ustr("m")], 1);
+		assert_eq!(res[&ustr("ü")], 1);
+		assert_eq!(res[&ustr("r")], 1);
+		assert_eq!(res[&ustr("e\u{301}")], 1);
+		assert_eq!(res.len(), 5);
+	}
+
+	#[test]
+	fn grapheme2() {
+		let opts = CountOptions {
+			unit: CountUnit::Grapheme,
+			ngram: NgramRange { min: 2, max: 2 },
+			joiner: String::new(),
+			..Default::default()
+		};
+		let mut swc = StreamWordCount::new(String::new());
+		swc.count_words("the then", &opts, &mut NgramWindow::default());
+		let res = swc.counts;
+
+		assert_eq!(res[&ustr("th")], 2);
+		assert_eq!(res[&ustr("he")], 2);
+		assert_eq!(res[&ustr("en")], 1);
+		assert_eq!(res.len(), 3);
+	}
+
+	#[test]
+	fn grapheme3() {
+		let opts = CountOptions {
+			unit: CountUnit::Grapheme,
+			ngram: NgramRange { min: 2, max: 2 },
+			joiner: String::new(),
+			include_whitespace: true,
+			include_punctuation: true,
+			..Default::default()
+		};
+		let mut swc = StreamWordCount::new(String::new());
+		swc.count_words("a b.", &opts, &mut NgramWindow::default());
+		let res = swc.counts;
+
+		assert_eq!(res[&ustr("a ")], 1);
+		assert_eq!(res[&ustr(" b")], 1);
+		assert_eq!(res[&ustr("b.")], 1);
+		assert_eq!(res.len(), 3);
+	}
+
+	#[test]
+	fn stem1() {
+		let opts = CountOptions {
+			stem: Some(StemLanguage::English),
+			..Default::default()
+		};
+		let mut swc = StreamWordCount::new(String::new());
+		swc.count_words(
+			"connect connected connecting connection",
+			&opts,
+			&mut NgramWindow::default(),
+		);
+
+		assert_eq!(swc.counts[&ustr("connect")], 4);
+		assert_eq!(swc.counts.len(), 1);
+		assert!(swc.forms.is_empty());
+	}
+
+	#[test]
+	fn stem2() {
+		let opts = CountOptions {
+			stem: Some(StemLanguage::English),
+			surface_forms: true,
+			..Default::default()
+		};
+		let mut swc1 = StreamWordCount::new(String::new());
+		swc1.count_words(
+			"connected connecting runs",
+			&opts,
+			&mut NgramWindow::default(),
+		);
+		let mut swc2 = StreamWordCount::new(String::new());
+		swc2.count_words("connecting run run", &opts, &mut NgramWindow::default());
+
+		let mut counts = [swc1, swc2];
+		StreamWordCount::use_surface_forms(&mut counts, StemLanguage::English);
+
+		assert_eq!(counts[0].counts[&ustr("connecting")], 2);
+		assert_eq!(counts[0].counts[&ustr("run")], 1);
+		assert_eq!(counts[1].counts[&ustr("connecting")], 1);
+		assert_eq!(counts[1].counts[&ustr("run")], 2);
+		assert_eq!(counts[0].counts.len(), 2);
+	}
+
+	#[test]
+	fn stem_mixed_case() {
+		let opts = CountOptions {
+			stem: Some(StemLanguage::English),
+			surface_forms: true,
+			..Default::default()
+		};
+		let mut swc = StreamWordCount::new(String::new());
+		swc.count_words(
+			"Connected connecting CONNECT connecting",
+			&opts,
+			&mut NgramWindow::default(),
+		);
+
+		assert_eq!(swc.counts[&ustr("connect")], 4);
+		assert_eq!(swc.counts.len(), 1);
+		assert_eq!(swc.forms[&ustr("Connected")], 1);
+		assert_eq!(swc.forms[&ustr("CONNECT")], 1);
+
+		let mut counts = [swc];
+		StreamWordCount::use_surface_forms(&mut counts, StemLanguage::English);
+
+		assert_eq!(counts[0].counts[&ustr("connecting")], 4);
+		assert_eq!(counts[0].counts.len(), 1);
+	}
+
+	#[test]
+	fn lemmas1() {
+		let content = "Went\tgo\ngoes\tgo\nmice\tmouse\n";
+		let mut stream = Stream::from_member(content.as_bytes(), String::new()).unwrap();
+		let normalizer = Normalizer {
+			case: CaseMode::Lower,
+			..Default::default()
+		};
+		let opts = CountOptions {
+			lemmas: Lemmas::from_stream(&mut stream, InputEncoding::Utf8, &normalizer),
+			normalizer,
+			..Default::default()
+		};
+		let mut swc = StreamWordCount::new(String::new());
+		swc.count_words(
+			"He went, she Goes, mice go.",
+			&opts,
+			&mut NgramWindow::default(),
+		);
+
+		assert_eq!(swc.counts[&ustr("go")], 3);
+		assert_eq!(swc.counts[&ustr("mouse")], 1);
+		assert_eq!(swc.counts[&ustr("he")], 1);
+		assert_eq!(swc.unlemmatized, 3);
+	}
+
+	#[test]
+	fn normalize1() {
+		let opts = CountOptions {
+			normalizer: Normalizer {
+				case: CaseMode::Fold,
+				form: Some(NormalForm::Nfc),
+				..Default::default()
+			},
+			..Default::default()
+		};
+		let mut swc = StreamWordCount::new(String::new());
+		swc.count_words(
+			"Straße STRASSE cafe\u{301} Café",
+			&opts,
+			&mut NgramWindow::default(),
+		);
+
+		assert_eq!(swc.counts[&ustr("strasse")], 2);
+		assert_eq!(swc.counts[&ustr("café")], 2);
+		assert_eq!(swc.counts.len(), 2);
+	}
+
+	#[test]
+	fn only_words1() {
+		let mut stream =
+			Stream::from_member(&b"lorem\nipsum dolor"[..], String::new()).unwrap();
+		let mut only_words = Exclusions::default();
+		only_words
+			.add_stream(&mut stream, InputEncoding::Utf8, &Normalizer::default())
+			.unwrap();
+
+		let opts = CountOptions {
+			ngram: NgramRange { min: 1, max: 2 },
+			only_words: Some(only_words),
+			..Default::default()
+		};
+		let mut swc = StreamWordCount::new(String::new());
+		swc.count_words(
+			"lorem sit lorem ipsum dolor",
+			&opts,
+			&mut NgramWindow::default(),
+		);
+
+		assert_eq!(swc.counts[&ustr("lorem")], 2);
+		assert_eq!(swc.counts[&ustr("ipsum")], 1);
+		assert_eq!(swc.counts[&ustr("dolor")], 1);
+		assert_eq!(swc.counts.len(), 3);
+	}
+
+	#[test]
+	fn exclusions1() {
+		let mut opts = CountOptions {
+			ngram: NgramRange { min: 1, max: 2 },
+			..Default::default()
+		};
+		opts.exclusions.add_words("the a", &Normalizer::default());
+
+		let mut swc = StreamWordCount::new(String::new());
+		swc.count_words("the cat saw a dog", &opts, &mut NgramWindow::default());
+
+		assert_eq!(swc.counts.get(&ustr("the")), None);
+		assert_eq!(swc.counts.get(&ustr("a")), None);
+		assert_eq!(swc.counts[&ustr("the cat")], 1);
+		assert_eq!(swc.counts[&ustr("cat")], 1);
+		assert_eq!(swc.counts.len(), 7);
+	}
+
+	#[test]
+	fn stream_chunks1() {
+		let text = "lorem ipsum dolor\n".repeat(20_000);
+		let stream = Stream::from_member(text.as_bytes(), String::from("test")).unwrap();
+
+		let res = StreamWordCount::from_stream(stream, &CountOptions::default()).unwrap();
+
+		assert_eq!(res.count(&ustr("lorem")), 20_000);
+		assert_eq!(res.count(&ustr("ipsum")), 20_000);
+		assert_eq!(res.count(&ustr("dolor")), 20_000);
+		assert_eq!(res.counts.len(), 3);
+	}
+
+	#[test]
+	fn stream_chunks2() {
+		let text = "ömür ğğğ 式 ".repeat(20_000);
+		let stream = Stream::from_member(text.as_bytes(), String::from("test")).unwrap();
+
+		let res = StreamWordCount::from_stream(stream, &CountOptions::default()).unwrap();
+
+		assert_eq!(res.count(&ustr("ömür")), 20_000);
+		assert_eq!(res.count(&ustr("ğğğ")), 20_000);
+		assert_eq!(res.count(&ustr("式")), 20_000);
+		assert_eq!(res.counts.len(), 3);
+	}
+
+	#[test]
+	fn stream_chunks3() {
+		let text = "lorem".repeat(300_000);
+		let stream = Stream::from_member(text.as_bytes(), String::from("test")).unwrap();
+
+		let res = StreamWordCount::from_stream(stream, &CountOptions::default()).unwrap();
+
+		assert_eq!(res.counts.values().sum::<usize>(), 2);
+	}
+
+	#[test]
+	fn stream_user_regex() {
+		// Larger than a piece, so a splittable stream would be counted in pieces.
+		let text = "lorem ipsum ".repeat(200_000);
+
+		let count = |pattern: &str| {
+			let stream =
+				Stream::from_member(text.as_bytes(), String::from("test")).unwrap();
+			let opts = CountOptions {
+				tokenizer: Tokenizer::from_user_regex(pattern, None).unwrap(),
+				..Default::default()
+			};
+
+			StreamWordCount::from_stream(stream, &opts).unwrap().counts
+		};
+
+		for pattern in [r"^\w+", r"\A\w+"] {
+			let res = count(pattern);
+			assert_eq!(res.len(), 1);
+			assert_eq!(res[&ustr("lorem")], 1);
+		}
+
+		let res = count(r"\w+ \w+");
+		assert_eq!(res.len(), 1);
+		assert_eq!(res[&ustr("lorem ipsum")], 200_000);
+	}
+
+	#[test]
+	fn batch_sizes() {
+		assert_eq!(batch_size(16, 1), 16 * PIECE_SIZE);
+		assert_eq!(batch_size(16, 4), 4 * PIECE_SIZE);
+		assert_eq!(batch_size(16, 5), 3 * PIECE_SIZE);
+		assert_eq!(batch_size(4, 16), PIECE_SIZE);
+		assert_eq!(batch_size(4, 0), 4 * PIECE_SIZE);
+	}
+
+	#[test]
+	fn split_pieces1() {
+		let res = StreamWordCount::split_pieces("lorem ipsum dolor sit amet", 4);
+
+		assert_eq!(res, vec!["lorem ", "ipsum ", "dolor ", "sit amet"]);
+	}
+
+	#[test]
+	fn split_pieces2() {
+		let res = StreamWordCount::split_pieces("ömür ğğğ 式 2d3", 3);
+
+		assert_eq!(res, vec!["ömür ", "ğğğ ", "式 ", "2d3"]);
+	}
+
+	#[test]
+	fn count_batch1() {
+		let text = "lorem ipsum dolor ömür\n".repeat(100_000);
+		let mut swc = StreamWordCount::new(String::new());
+		swc.count_batch(&text, &CountOptions::default(), &mut NgramWindow::default());
+		let res = swc.counts;
+
+		assert_eq!(res[&ustr("lorem")], 100_000);
+		assert_eq!(res[&ustr("ipsum")], 100_000);
+		assert_eq!(res[&ustr("dolor")], 100_000);
+		assert_eq!(res[&ustr("ömür")], 100_000);
+		assert_eq!(res.len(), 4);
+	}
+
 	#[test]
 	fn merge_maps1() {
 		let mut map1 = HashMap::from([("lorem", 3), ("ipsum", 2), ("dolor", 17)]);
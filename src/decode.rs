@@ -0,0 +1,190 @@
+use std::io::{self, ErrorKind};
+
+use encoding_rs::{Decoder, DecoderResult, Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+
+use crate::args::InputEncoding;
+
+/// Number of bytes that are inspected while guessing the encoding of a stream.
+const SNIFF_LEN: usize = 1024;
+
+/// Decodes the bytes of a stream into text, chunk by chunk.
+///
+/// Byte order marks are detected and removed in every mode.
+pub struct TextDecoder {
+	encoding: InputEncoding,
+	/// Created with the first chunk, as `InputEncoding::Auto` needs to inspect it.
+	decoder: Option<Decoder>,
+	/// Number of malformed sequences that are replaced with U+FFFD.
+	replacements: usize,
+}
+
+impl TextDecoder {
+	/// Creates a `TextDecoder` for the given encoding.
+	pub fn new(encoding: InputEncoding) -> Self {
+		TextDecoder {
+			encoding,
+			decoder: None,
+			replacements: 0,
+		}
+	}
+
+	/// Decodes `src` and appends the result to `dst`.
+	///
+	/// Incomplete sequences at the end of `src` are completed with the next call. `last` must be
+	/// set with the last chunk of the stream.
+	pub fn decode(&mut self, src: &[u8], dst: &mut String, last: bool) -> io::Result<()> {
+		let lossy = !matches!(self.encoding, InputEncoding::Utf8);
+		let decoder = self
+			.decoder
+			.get_or_insert_with(|| Self::guess(self.encoding, src).new_decoder());
+
+		let mut src = src;
+
+		loop {
+			if let Some(len) =
+				decoder.max_utf8_buffer_length_without_replacement(src.len())
+			{
+				dst.reserve(len);
+			}
+
+			let (res, read) =
+				decoder.decode_to_string_without_replacement(src, dst, last);
+			src = &src[read..];
+
+			match res {
+				DecoderResult::InputEmpty => return Ok(()),
+				DecoderResult::OutputFull => dst.reserve(src.len().max(4)),
+				DecoderResult::Malformed(_, _) if lossy => {
+					dst.push(char::REPLACEMENT_CHARACTER);
+					self.replacements += 1;
+				}
+				DecoderResult::Malformed(_, _) => {
+					return Err(io::Error::new(
+						ErrorKind::InvalidData,
+						"stream did not contain valid UTF-8",
+					))
+				}
+			}
+		}
+	}
+
+	/// Returns the number of malformed sequences that are replaced with U+FFFD so far.
+	#[inline]
+	pub fn replacements(&self) -> usize {
+		self.replacements
+	}
+
+	/// Returns the `Encoding` to decode the stream with, `head` being its first chunk.
+	///
+	/// `InputEncoding::Auto` results in UTF-16 if every other byte is mostly zero, UTF-8 if the
+	/// start of the stream is valid UTF-8 and Windows-1252 if not.
+	fn guess(encoding: InputEncoding, head: &[u8]) -> &'static Encoding {
+		match encoding {
+			InputEncoding::Utf8 | InputEncoding::Utf8Lossy => UTF_8,
+			InputEncoding::Latin1 => WINDOWS_1252,
+			InputEncoding::Utf16le => UTF_16LE,
+			InputEncoding::Utf16be => UTF_16BE,
+			InputEncoding::Auto => {
+				if let Some((enc, _)) = Encoding::for_bom(head) {
+					return enc;
+				}
+
+				let head = &head[..head.len().min(SNIFF_LEN)];
+				let pairs = head.len() / 2;
+				let even_zeros =
+					head.iter().step_by(2).filter(|b| **b == 0).count();
+				let odd_zeros =
+					head.iter().skip(1).step_by(2).filter(|b| **b == 0).count();
+
+				if pairs > 0 && odd_zeros * 2 > pairs && even_zeros * 8 < pairs {
+					UTF_16LE
+				} else if pairs > 0
+					&& even_zeros * 2 > pairs && odd_zeros * 8 < pairs
+				{
+					UTF_16BE
+				} else if Self::is_utf8_prefix(head) {
+					UTF_8
+				} else {
+					WINDOWS_1252
+				}
+			}
+		}
+	}
+
+	/// Checks if `head` is valid UTF-8.
+	///
+	/// An incomplete character at the end is accepted if `head` is cut at `SNIFF_LEN`, as the
+	/// rest of the character is not inspected.
+	#[inline]
+	fn is_utf8_prefix(head: &[u8]) -> bool {
+		match std::str::from_utf8(head) {
+			Ok(_) => true,
+			Err(e) => e.error_len().is_none() && head.len() == SNIFF_LEN,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn decode_all(encoding: InputEncoding, chunks: &[&[u8]]) -> io::Result<(String, usize)> {
+		let mut decoder = TextDecoder::new(encoding);
+		let mut res = String::new();
+
+		for c in chunks {
+			decoder.decode(c, &mut res, false)?;
+		}
+		decoder.decode(&[], &mut res, true)?;
+
+		Ok((res, decoder.replacements()))
+	}
+
+	#[test]
+	fn utf8() {
+		let res = decode_all(InputEncoding::Utf8, &[b"lorem \xc3", b"\xb6m\xc3\xbcr"])
+			.unwrap();
+
+		assert_eq!(res, (String::from("lorem ömür"), 0));
+	}
+
+	#[test]
+	fn utf8_invalid() {
+		let res = decode_all(InputEncoding::Utf8, &[b"lorem \xff ipsum"]);
+
+		assert!(res.is_err());
+	}
+
+	#[test]
+	fn utf8_lossy() {
+		let res = decode_all(InputEncoding::Utf8Lossy, &[b"lorem \xff ips\xfeum"]).unwrap();
+
+		assert_eq!(res, (String::from("lorem \u{fffd} ips\u{fffd}um"), 2));
+	}
+
+	#[test]
+	fn latin1() {
+		let res = decode_all(InputEncoding::Latin1, &[b"caf\xe9 na\xefve"]).unwrap();
+
+		assert_eq!(res, (String::from("café naïve"), 0));
+	}
+
+	#[test]
+	fn utf16_bom() {
+		let res = decode_all(InputEncoding::Utf16be, &[b"\xff\xfel\x00o\x00"]).unwrap();
+
+		assert_eq!(res, (String::from("lo"), 0));
+	}
+
+	#[test]
+	fn auto() {
+		let utf8 = decode_all(InputEncoding::Auto, &["ömür".as_bytes()]).unwrap();
+		let latin1 = decode_all(InputEncoding::Auto, &[b"caf\xe9"]).unwrap();
+		let utf16 =
+			decode_all(InputEncoding::Auto, &[b"l\x00o\x00r\x00e\x00m\x00"]).unwrap();
+
+		assert_eq!(utf8, (String::from("ömür"), 0));
+		assert_eq!(latin1, (String::from("café"), 0));
+		assert_eq!(utf16, (String::from("lorem"), 0));
+	}
+}
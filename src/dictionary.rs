@@ -0,0 +1,185 @@
+use std::collections::HashSet;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{
+	args::InputEncoding, normalize::Normalizer, regexes::SEGMENTABLE_REGEX, stream::Stream,
+};
+
+/// Set of words that is used to segment the scripts that are written without spaces, such as
+/// Chinese, Japanese and Thai.
+#[derive(Debug, Clone)]
+pub struct Dictionary {
+	words: HashSet<String>,
+	/// Length of the longest word, in grapheme clusters.
+	max_len: usize,
+}
+
+impl Dictionary {
+	/// Creates Dictionary from a `Stream`.
+	///
+	/// The first whitespace seperated field of every line is a word, the rest of the line is
+	/// ignored. This allows word lists with frequencies or part of speech tags to be used as is.
+	///
+	/// The stream is decoded with `encoding`, and the words are normalized with `normalizer`, so
+	/// they match the normalized text.
+	pub fn from_stream(
+		stream: &mut Stream,
+		encoding: InputEncoding,
+		normalizer: &Normalizer,
+	) -> Option<Self> {
+		let mut content = String::new();
+
+		stream.read_to_string(encoding, &mut content)?;
+		let content = normalizer.normalize(&content);
+
+		Some(Self::from_words(
+			content.lines().filter_map(|l| l.split_whitespace().next()),
+		))
+	}
+
+	/// Creates Dictionary from an iterator of words.
+	pub fn from_words<'a, I>(iter: I) -> Self
+	where
+		I: Iterator<Item = &'a str>,
+	{
+		let words: HashSet<String> = iter.map(str::to_string).collect();
+		let max_len = words
+			.iter()
+			.map(|w| w.graphemes(true).count())
+			.max()
+			.unwrap_or(0);
+
+		Dictionary { words, max_len }
+	}
+
+	/// Calls `f` with every word in `s`.
+	///
+	/// Runs of segmentable scripts are split by forward maximum matching, the rest of the text is
+	/// passed to `other`.
+	pub fn for_each_token<'a, F, O>(&self, s: &'a str, mut f: F, mut other: O)
+	where
+		F: FnMut(&'a str),
+		O: FnMut(&'a str, &mut F),
+	{
+		let mut last = 0;
+
+		for m in SEGMENTABLE_REGEX.find_iter(s) {
+			other(&s[last..m.start()], &mut f);
+			self.segment(m.as_str(), &mut f);
+			last = m.end();
+		}
+
+		other(&s[last..], &mut f);
+	}
+
+	/// Splits `s` by forward maximum matching. At every position, the longest word in the
+	/// dictionary is taken, or a single grapheme cluster if there are none, so the combining
+	/// marks stay with their base characters.
+	fn segment<'a, F>(&self, s: &'a str, f: &mut F)
+	where
+		F: FnMut(&'a str),
+	{
+		// Byte offsets of the grapheme cluster boundaries, including the end of `s`.
+		let bounds: Vec<usize> = s
+			.grapheme_indices(true)
+			.map(|(i, _)| i)
+			.chain(std::iter::once(s.len()))
+			.collect();
+
+		let mut i = 0;
+
+		while i + 1 < bounds.len() {
+			let longest = (i + 2..bounds.len().min(i + self.max_len + 1))
+				.rev()
+				.find(|j| self.words.contains(&s[bounds[i]..bounds[*j]]))
+				.unwrap_or(i + 1);
+
+			f(&s[bounds[i]..bounds[longest]]);
+			i = longest;
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::args::NormalForm;
+
+	fn tokens(dict: &Dictionary, s: &str) -> Vec<String> {
+		let mut res = Vec::new();
+		dict.for_each_token(
+			s,
+			|t| res.push(t.to_string()),
+			|o, f| {
+				o.split(|c: char| !c.is_alphanumeric())
+					.filter(|w| !w.is_empty())
+					.for_each(f)
+			},
+		);
+
+		res
+	}
+
+	#[test]
+	fn chinese() {
+		let dict = Dictionary::from_words(
+			["我们", "喜欢", "机器", "机器学习", "学习"].into_iter(),
+		);
+
+		assert_eq!(
+			tokens(&dict, "我们喜欢机器学习"),
+			vec!["我们", "喜欢", "机器学习"]
+		);
+	}
+
+	#[test]
+	fn japanese() {
+		let dict = Dictionary::from_words(["東京", "大学", "東京大学", "です"].into_iter());
+
+		assert_eq!(
+			tokens(&dict, "東京大学です。大学"),
+			vec!["東京大学", "です", "大学"]
+		);
+	}
+
+	#[test]
+	fn thai() {
+		let dict = Dictionary::from_words(["สวัสดี", "ครับ"].into_iter());
+
+		assert_eq!(tokens(&dict, "สวัสดีครับ"), vec!["สวัสดี", "ครับ"]);
+	}
+
+	#[test]
+	fn unknown() {
+		let dict = Dictionary::from_words(["机器"].into_iter());
+
+		assert_eq!(
+			tokens(&dict, "lorem 机器人 ipsum"),
+			vec!["lorem", "机器", "人", "ipsum"]
+		);
+	}
+
+	#[test]
+	fn unknown_combining() {
+		let dict = Dictionary::from_words(["ข้าว"].into_iter());
+
+		assert_eq!(tokens(&dict, "กินข้าว"), vec!["กิ", "น", "ข้าว"]);
+	}
+
+	#[test]
+	fn normalized() {
+		let normalizer = Normalizer {
+			form: Some(NormalForm::Nfkc),
+			..Default::default()
+		};
+		let mut stream = Stream::from_member("ｶﾚｰ 10\n".as_bytes(), String::new()).unwrap();
+		let dict = Dictionary::from_stream(&mut stream, InputEncoding::Utf8, &normalizer)
+			.unwrap();
+
+		assert_eq!(
+			tokens(&dict, "カレーライス"),
+			vec!["カレー", "ラ", "イ", "ス"]
+		);
+	}
+}
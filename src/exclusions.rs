@@ -1,30 +1,259 @@
+use std::collections::HashSet;
+
+use globset::{GlobBuilder, GlobMatcher};
+use regex::{Regex, RegexBuilder};
 use ustr::{ustr, Ustr, UstrSet};
 
-use crate::stream::Stream;
+use crate::{
+	args::InputEncoding,
+	normalize::{CaseMode, Normalizer},
+	stream::Stream,
+	wprintln,
+};
 
-/// Wrapper around the UstrSet that represents a set of words that are excluded from reading.
+/// Set of words, regular expressions and globs that are excluded from reading.
+///
+/// Also used as the list of words that are only counted, for `--only-words`.
+#[derive(Debug, Clone, Default)]
 pub struct Exclusions {
 	words: UstrSet,
+	/// Regular expressions that match the whole word.
+	regexes: Vec<Regex>,
+	globs: Vec<GlobMatcher>,
+	/// Stop words, that match the words case insensitively.
+	stopwords: HashSet<String>,
+	/// Normalizer that the words are case mapped with, before they are looked up in `stopwords`.
+	stopword_normalizer: Normalizer,
 }
 
 impl Exclusions {
-	/// Creates Exclusions from a `Stream`.
-	pub fn from_stream(stream: &mut Stream) -> Option<Self> {
+	/// Adds the rules in a `Stream` to the set.
+	///
+	/// Every line is either:
+	/// - a comment, if it starts with `#`,
+	/// - a regular expression that matches the whole word, if it starts with `re:`,
+	/// - a glob, such as `http*`, if it starts with `glob:`,
+	/// - or whitespace seperated words.
+	///
+	/// A `#` after a whitespace starts a comment that lasts until the end of the line.
+	///
+	/// The stream is decoded with `encoding`, as the counted streams are.
+	///
+	/// Words are normalized with `normalizer`, so they match the counted words. Regular expressions
+	/// and globs are normalized too, except for the case mapping that would change escapes such
+	/// as `\W`, instead they ignore case if the counted words are case mapped. Invalid regular
+	/// expressions and globs are skipped with a warning.
+	pub fn add_stream(
+		&mut self,
+		stream: &mut Stream,
+		encoding: InputEncoding,
+		normalizer: &Normalizer,
+	) -> Option<()> {
 		let mut content = String::new();
 
-		stream.read_to_string(&mut content)?;
-		let mut words = UstrSet::default();
+		stream.read_to_string(encoding, &mut content)?;
+		let ignore_case = normalizer.case != CaseMode::Preserve;
+		let pattern_normalizer = Normalizer {
+			case: CaseMode::Preserve,
+			..normalizer.clone()
+		};
+
+		for (i, line) in content.lines().enumerate() {
+			let line = strip_comment(line).trim();
+
+			if line.is_empty() {
+				continue;
+			}
 
-		for s in content.split_ascii_whitespace() {
-			words.insert(ustr(s));
+			if let Some(re) = line.strip_prefix("re:") {
+				let re = pattern_normalizer.normalize(re.trim());
+
+				match RegexBuilder::new(&format!("^(?:{})$", re))
+					.case_insensitive(ignore_case)
+					.build()
+				{
+					Ok(re) => self.regexes.push(re),
+					Err(e) => {
+						wprintln!("{}:{}: {}", stream.name(), i + 1, e);
+					}
+				}
+			} else if let Some(glob) = line.strip_prefix("glob:") {
+				let glob = pattern_normalizer.normalize(glob.trim());
+
+				match GlobBuilder::new(&glob)
+					.case_insensitive(ignore_case)
+					.build()
+				{
+					Ok(glob) => self.globs.push(glob.compile_matcher()),
+					Err(e) => {
+						wprintln!("{}:{}: {}", stream.name(), i + 1, e);
+					}
+				}
+			} else {
+				self.add_words(line, normalizer);
+			}
 		}
 
-		Some(Exclusions { words })
+		Some(())
+	}
+
+	/// Adds the whitespace seperated words in `content` to the set.
+	///
+	/// Words are normalized with `normalizer`, so they match the counted words.
+	pub fn add_words(&mut self, content: &str, normalizer: &Normalizer) {
+		for s in normalizer.normalize(content).split_ascii_whitespace() {
+			self.words.insert(ustr(s));
+		}
+	}
+
+	/// Adds the whitespace seperated stop words in `content` to the set.
+	///
+	/// Unlike the other words, stop words are matched case insensitively even if the counted
+	/// words are not case mapped, so `The` is excluded as well as `the`.
+	pub fn add_stopwords(&mut self, content: &str, normalizer: &Normalizer) {
+		self.stopword_normalizer = Normalizer {
+			case: match normalizer.case {
+				CaseMode::Preserve => CaseMode::Lower,
+				case => case,
+			},
+			..normalizer.clone()
+		};
+
+		let content = self.stopword_normalizer.normalize(content);
+		self.stopwords
+			.extend(content.split_ascii_whitespace().map(str::to_string));
 	}
 
-	/// Checks if the word `s` is in the set.
+	/// Returns an iterator of the words in the set. Regular expressions and globs are not
+	/// included.
+	pub fn words(&self) -> impl Iterator<Item = &Ustr> {
+		self.words.iter()
+	}
+
+	/// Checks if the word `s` is excluded.
 	#[inline]
 	pub fn contains(&self, s: &Ustr) -> bool {
 		self.words.contains(s)
+			|| self.regexes.iter().any(|r| r.is_match(s))
+			|| self.globs.iter().any(|g| g.is_match(s.as_str()))
+			|| (!self.stopwords.is_empty()
+				&& self.stopwords
+					.contains(self.stopword_normalizer.normalize(s).as_ref()))
+	}
+}
+
+/// Removes the comment from `line`, that starts with a `#` at the start of the line or after a
+/// whitespace.
+fn strip_comment(line: &str) -> &str {
+	let start = line
+		.match_indices('#')
+		.map(|(i, _)| i)
+		.find(|i| *i == 0 || line[..*i].ends_with(char::is_whitespace));
+
+	match start {
+		Some(i) => &line[..i],
+		None => line,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn exclusions(content: &str, normalizer: &Normalizer) -> Exclusions {
+		let mut stream = Stream::from_member(content.as_bytes(), String::new()).unwrap();
+		let mut exclusions = Exclusions::default();
+		exclusions
+			.add_stream(&mut stream, InputEncoding::Utf8, normalizer)
+			.unwrap();
+
+		exclusions
+	}
+
+	#[test]
+	fn words() {
+		let exclusions = exclusions("lorem ipsum\ndolor", &Normalizer::default());
+
+		assert!(exclusions.contains(&ustr("lorem")));
+		assert!(exclusions.contains(&ustr("ipsum")));
+		assert!(exclusions.contains(&ustr("dolor")));
+		assert!(!exclusions.contains(&ustr("sit")));
+	}
+
+	#[test]
+	fn rules() {
+		let content = "# numbers and links\nre: [0-9]+ # digits\nglob:http*\nlorem # a comment\nC#";
+		let exclusions = exclusions(content, &Normalizer::default());
+
+		assert!(exclusions.contains(&ustr("2024")));
+		assert!(!exclusions.contains(&ustr("2d3")));
+		assert!(exclusions.contains(&ustr("https://example.com")));
+		assert!(exclusions.contains(&ustr("lorem")));
+		assert!(exclusions.contains(&ustr("C#")));
+		assert!(!exclusions.contains(&ustr("#")));
+		assert!(!exclusions.contains(&ustr("comment")));
+		assert!(!exclusions.contains(&ustr("numbers")));
+		assert!(!exclusions.contains(&ustr("HTTP")));
+	}
+
+	#[test]
+	fn rules_case_insensitive() {
+		let normalizer = Normalizer {
+			case: CaseMode::Lower,
+			..Default::default()
+		};
+		let exclusions = exclusions("re:[A-Z]+\nglob:HTTP*\nLorem", &normalizer);
+
+		assert!(exclusions.contains(&ustr("ipsum")));
+		assert!(exclusions.contains(&ustr("http")));
+		assert!(exclusions.contains(&ustr("lorem")));
+		assert!(!exclusions.contains(&ustr("ipsum2")));
+	}
+
+	#[test]
+	fn rules_normalized() {
+		let normalizer = Normalizer {
+			case: CaseMode::Lower,
+			strip_diacritics: true,
+			..Default::default()
+		};
+		let exclusions = exclusions("glob:Café*\nre:\\W*ÉTÉ", &normalizer);
+
+		assert!(exclusions.contains(&ustr("cafeteria")));
+		assert!(exclusions.contains(&ustr("ete")));
+		assert!(exclusions.contains(&ustr("--ete")));
+		assert!(!exclusions.contains(&ustr("lorem_ete")));
+	}
+
+	#[test]
+	fn stopwords() {
+		let mut exclusions = Exclusions::default();
+		exclusions.add_stopwords("the\nand", &Normalizer::default());
+
+		assert!(exclusions.contains(&ustr("the")));
+		assert!(exclusions.contains(&ustr("The")));
+		assert!(exclusions.contains(&ustr("AND")));
+		assert!(!exclusions.contains(&ustr("Lorem")));
+	}
+
+	#[test]
+	fn decoded() {
+		let mut stream =
+			Stream::from_member(&b"\xEF\xBB\xBFThe\ncat"[..], String::new()).unwrap();
+		let mut exclusions = Exclusions::default();
+		exclusions
+			.add_stream(&mut stream, InputEncoding::Utf8, &Normalizer::default())
+			.unwrap();
+
+		assert!(exclusions.contains(&ustr("The")));
+		assert!(exclusions.contains(&ustr("cat")));
+
+		let mut stream = Stream::from_member(&b"caf\xE9"[..], String::new()).unwrap();
+		let mut exclusions = Exclusions::default();
+		exclusions
+			.add_stream(&mut stream, InputEncoding::Latin1, &Normalizer::default())
+			.unwrap();
+
+		assert!(exclusions.contains(&ustr("café")));
 	}
 }
@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+
+use crate::{args::InputEncoding, normalize::Normalizer, stream::Stream, wprintln};
+
+/// Maps the forms of the words to their lemmas.
+#[derive(Debug, Clone, Default)]
+pub struct Lemmas {
+	lemmas: HashMap<String, String>,
+}
+
+impl Lemmas {
+	/// Creates Lemmas from a `Stream`.
+	///
+	/// Every line is a form and its lemma, seperated by a tab. The rest of the line, such as part
+	/// of speech tags, is ignored. Empty lines and the lines starting with `#` are skipped. If a
+	/// form is listed more than once, the first lemma is used.
+	///
+	/// The stream is decoded with `encoding`, and the forms and lemmas are normalized with
+	/// `normalizer`, as the counted words are.
+	pub fn from_stream(
+		stream: &mut Stream,
+		encoding: InputEncoding,
+		normalizer: &Normalizer,
+	) -> Option<Self> {
+		let mut content = String::new();
+
+		stream.read_to_string(encoding, &mut content)?;
+		let content = normalizer.normalize(&content);
+
+		let mut lemmas = HashMap::new();
+
+		for (i, line) in content.lines().enumerate() {
+			if line.trim().is_empty() || line.starts_with('#') {
+				continue;
+			}
+
+			let mut fields = line.split('\t');
+			match (fields.next(), fields.next()) {
+				(Some(form), Some(lemma))
+					if !form.is_empty() && !lemma.is_empty() =>
+				{
+					lemmas.entry(form.to_string())
+						.or_insert_with(|| lemma.to_string());
+				}
+				_ => {
+					wprintln!(
+						"{}:{}: Expected a form and a lemma seperated by a tab",
+						stream.name(),
+						i + 1
+					);
+				}
+			}
+		}
+
+		Some(Lemmas { lemmas })
+	}
+
+	/// Returns the lemma of the word `s`, if there is any.
+	#[inline]
+	pub fn get(&self, s: &str) -> Option<&str> {
+		self.lemmas.get(s).map(String::as_str)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::normalize::CaseMode;
+
+	#[test]
+	fn from_stream1() {
+		let content =
+			"# form\tlemma\nwent\tgo\tVERB\ngoes\tgo\n\nmice\tmouse\nmice\tmice\n";
+		let mut stream = Stream::from_member(content.as_bytes(), String::new()).unwrap();
+		let lemmas = Lemmas::from_stream(
+			&mut stream,
+			InputEncoding::Utf8,
+			&Normalizer::default(),
+		)
+		.unwrap();
+
+		assert_eq!(lemmas.get("went"), Some("go"));
+		assert_eq!(lemmas.get("goes"), Some("go"));
+		assert_eq!(lemmas.get("mice"), Some("mouse"));
+		assert_eq!(lemmas.get("# form"), None);
+		assert_eq!(lemmas.get("go"), None);
+	}
+
+	#[test]
+	fn from_stream2() {
+		let content = "Went\tGo\n";
+		let mut stream = Stream::from_member(content.as_bytes(), String::new()).unwrap();
+		let normalizer = Normalizer {
+			case: CaseMode::Lower,
+			..Default::default()
+		};
+		let lemmas =
+			Lemmas::from_stream(&mut stream, InputEncoding::Utf8, &normalizer).unwrap();
+
+		assert_eq!(lemmas.get("went"), Some("go"));
+		assert_eq!(lemmas.get("Went"), None);
+	}
+
+	#[test]
+	fn decoded() {
+		let content = b"\xFF\xFEw\x00e\x00n\x00t\x00\t\x00g\x00o\x00";
+		let mut stream = Stream::from_member(&content[..], String::new()).unwrap();
+		let lemmas = Lemmas::from_stream(
+			&mut stream,
+			InputEncoding::Utf16le,
+			&Normalizer::default(),
+		)
+		.unwrap();
+
+		assert_eq!(lemmas.get("went"), Some("go"));
+	}
+}
@@ -1,20 +1,35 @@
+mod archive;
 mod args;
 mod count;
+mod decode;
+mod dictionary;
 mod exclusions;
+mod lemmas;
+mod normalize;
+mod output;
 mod regexes;
+mod stopwords;
 mod stream;
+mod tokenizer;
+mod walk;
 mod warning;
-use std::fmt::Write;
 use std::io::BufWriter;
 use std::{io, process::ExitCode};
 
 use anyhow::{anyhow, Context, Result};
-use args::WordRegex;
+use archive::Archive;
 use clap::Parser;
 use count::*;
+use dictionary::Dictionary;
 use exclusions::Exclusions;
+use lemmas::Lemmas;
+use output::Report;
+use rayon::{prelude::*, ThreadPoolBuilder};
+use stopwords::stopwords;
 use stream::Stream;
+use tokenizer::Tokenizer;
 use ustr::Ustr;
+use walk::{resolve_inputs, Input};
 use warning::warning_printed;
 
 enum Return {
@@ -39,19 +54,128 @@ fn main() -> ExitCode {
 fn run() -> Result<()> {
 	let args = args::Cli::try_parse()?; // CLI arguments
 
+	if let Some(lang) = args.print_stopwords {
+		print!("{}", stopwords(lang));
+
+		return Ok(());
+	}
+
 	let files = &args.files;
 
 	if files.is_empty() {
 		return Err(anyhow!("No files entered"));
 	}
 
-	let counts = get_counts(files, args.pattern, args.case_insensitive, args.werror)?;
+	let inputs = resolve_inputs(files, args.walk_options().as_ref())?;
+
+	if args.list_files {
+		for f in inputs.iter().flat_map(|i| i.files()) {
+			println!("{}", f);
+		}
+
+		return Ok(());
+	}
+
+	let mut tokenizer = args.tokenizer()?;
+
+	if let Some(s) = &args.dictionary {
+		let mut dictionary_stream =
+			Stream::from_str(s).context("Can't read --dictionary file")?;
+
+		let dictionary = Dictionary::from_stream(
+			&mut dictionary_stream,
+			args.encoding,
+			&args.normalizer(),
+		)
+		.context("Can't read --dictionary file")?;
+
+		tokenizer = Tokenizer::Dictionary(dictionary, Box::new(tokenizer));
+	}
+
+	if args.surface_forms && args.ngram.max > 1 {
+		return Err(anyhow!("--surface-forms can't be used with n-grams"));
+	}
+
+	let mut count_options = args.count_options(tokenizer);
+
+	if let Some(s) = &args.lemmas {
+		let mut lemmas_stream = Stream::from_str(s).context("Can't read --lemmas file")?;
+
+		let lemmas =
+			Lemmas::from_stream(&mut lemmas_stream, args.encoding, &args.normalizer())
+				.context("Can't read --lemmas file")?;
+
+		count_options.lemmas = Some(lemmas);
+	}
+
+	for s in args.excluded_words.iter() {
+		let mut exclude_stream =
+			Stream::from_str(s).context("Can't read --excluded-words file")?;
+
+		count_options
+			.exclusions
+			.add_stream(&mut exclude_stream, args.encoding, &args.normalizer())
+			.context("Can't read --excluded-words file")?;
+	}
+
+	for lang in args.stopwords.iter() {
+		count_options
+			.exclusions
+			.add_stopwords(stopwords(*lang), &args.normalizer());
+	}
+
+	if let Some(s) = &args.only_words {
+		let mut only_stream =
+			Stream::from_str(s).context("Can't read --only-words file")?;
+
+		let mut only_words = Exclusions::default();
+		only_words
+			.add_stream(&mut only_stream, args.encoding, &args.normalizer())
+			.context("Can't read --only-words file")?;
+
+		count_options.only_words = Some(only_words);
+	}
+
+	let pool = ThreadPoolBuilder::new()
+		.num_threads(args.jobs.unwrap_or(0))
+		.build()
+		.context("Can't create the thread pool")?;
+
+	let mut counts = pool.install(|| {
+		get_counts(
+			&inputs,
+			&count_options,
+			args.aggregate_archives,
+			args.werror,
+		)
+	})?;
 
 	if counts.is_empty() {
 		return Err(anyhow!("Args does not contain any valid files to process"));
 	}
 
-	let total = TotalCount::from_counts(counts.iter());
+	if args.lemmas.is_some() {
+		for c in counts.iter().filter(|c| c.unlemmatized > 0) {
+			eprintln!("{}: {} words had no lemma entry", c.label(), c.unlemmatized);
+		}
+	}
+
+	if let (Some(stem), true) = (args.stem, args.surface_forms) {
+		StreamWordCount::use_surface_forms(&mut counts, stem);
+	}
+
+	let mut total = TotalCount::from_counts(counts.iter());
+
+	// Listed words are displayed even if they are not found, so the rows are the same for
+	// every run.
+	if let Some(only_words) = &count_options.only_words {
+		for w in only_words
+			.words()
+			.filter(|w| !count_options.exclusions.contains(w))
+		{
+			total.counts.entry(*w).or_insert(0);
+		}
+	}
 
 	let display_total = args.display_total.should_display(counts.len());
 
@@ -71,56 +195,37 @@ fn run() -> Result<()> {
 			.collect()
 	};
 
-	let words_to_print: Vec<_> = if let Some(s) = args.excluded_words {
-		let mut exclude_stream =
-			Stream::from_str(&s).context("Can't read --excluded-words file")?;
-
-		let exclusions = Exclusions::from_stream(&mut exclude_stream)
-			.context("Can't read --excluded-words file")?;
-
-		words_to_print
-			.into_iter()
-			.filter(|(s, _)| !exclusions.contains(s))
-			.collect()
-	} else {
-		words_to_print
+	let report = Report {
+		counts: &counts,
+		total: &total,
+		rows: &words_to_print,
+		display_total,
+		total_label: &args.total_label,
+		csv: args.csv_options(),
 	};
 
-	output_csv(counts, words_to_print, display_total, &args.total_label)?;
+	report.write(args.format, &mut BufWriter::new(io::stdout().lock()))?;
 
 	Ok(())
 }
 
 fn get_counts(
-	files: &Vec<String>,
-	pattern: WordRegex,
-	case_insensitive: bool,
+	inputs: &[Input],
+	count_options: &CountOptions,
+	aggregate_archives: bool,
 	werror: bool,
 ) -> Result<Vec<StreamWordCount>> {
-	let counts = match werror {
-		true => files
-			.iter()
-			.map_while(|f| Stream::from_str(f))
-			.map_while(|s| {
-				StreamWordCount::from_stream(
-					s,
-					pattern.to_regex(),
-					case_insensitive,
-				)
-			})
-			.collect(),
-		false => files
-			.iter()
-			.filter_map(|f| Stream::from_str(f))
-			.filter_map(|s| {
-				StreamWordCount::from_stream(
-					s,
-					pattern.to_regex(),
-					case_insensitive,
-				)
-			})
-			.collect(),
-	};
+	if werror && warning_printed() {
+		return Err(anyhow!("--werror: Processes stopped early due to warnings"));
+	}
+
+	let counts = count_inputs(
+		inputs,
+		count_options,
+		aggregate_archives,
+		werror,
+		&warning_printed,
+	);
 
 	//warning_printed()
 
@@ -131,51 +236,238 @@ fn get_counts(
 	Ok(counts)
 }
 
-fn output_csv(
-	counts: Vec<StreamWordCount>,
-	words_to_print: Vec<(Ustr, usize)>,
-	display_total: bool,
-	total_label: &str,
-) -> Result<()> {
-	// Using two buffers.
-	// `out_buf` is used to avoid unnecessary allocations caused by the `to_string`
-	// method of the numeric types. The line is formatted here before pushing to writer.
-	let mut out_buf = String::new();
-	// Buffered writer to stdout. Much faster than printing to stdout directly.
-	let mut writer = BufWriter::new(io::stdout().lock());
+/// Counts the `inputs` in parallel, and returns the counts in their order.
+///
+/// If `werror` is set, the inputs that are not started yet are skipped once `warned` returns
+/// true, and the counts after the first input that failed are dropped.
+fn count_inputs(
+	inputs: &[Input],
+	count_options: &CountOptions,
+	aggregate_archives: bool,
+	werror: bool,
+	warned: &(dyn Fn() -> bool + Sync),
+) -> Vec<StreamWordCount> {
+	let count_stream = |s: Stream| StreamWordCount::from_stream(s, count_options);
+
+	// Counts the file at path `f` and passes the results to `emit`.
+	// Archives result in a count for every member, unless `aggregate_archives` is set.
+	let count_file = |f: &str, emit: &mut dyn FnMut(StreamWordCount)| -> Option<()> {
+		match Archive::from_stream(Stream::from_str(f)?) {
+			Err(stream) => {
+				emit(count_stream(stream)?);
+				Some(())
+			}
+			Ok(archive) if aggregate_archives => {
+				let mut total = StreamWordCount::new(archive.label().to_string());
+				let res = archive.for_each_member(werror, |s| {
+					total.merge(&count_stream(s)?);
+					Some(())
+				});
+				emit(total);
+
+				res
+			}
+			Ok(archive) => archive.for_each_member(werror, |s| {
+				emit(count_stream(s)?);
+				Some(())
+			}),
+		}
+	};
+
+	// Counts every stream in the `input`. Archives may result in more than one count.
+	let count_input = |input: &Input| -> (Vec<StreamWordCount>, Option<()>) {
+		let mut counts = Vec::new();
+
+		// `--werror` stops the inputs that are not started yet, after a warning.
+		if werror && warned() {
+			return (counts, None);
+		}
+
+		let res = match input {
+			Input::Path(f) => count_file(f, &mut |c| counts.push(c)),
+			Input::Directory(d, files) => {
+				let (total, res) = files
+					.par_iter()
+					.map(|f| {
+						let mut total = StreamWordCount::new(d.clone());
+
+						if werror && warned() {
+							return (total, None);
+						}
+
+						let res = count_file(f, &mut |c| total.merge(&c));
+						(total, res)
+					})
+					.reduce(
+						|| (StreamWordCount::new(d.clone()), Some(())),
+						|(mut a, a_res), (b, b_res)| {
+							a.merge(&b);
+							(a, a_res.and(b_res))
+						},
+					);
+				counts.push(total);
+
+				res
+			}
+		};
+
+		(counts, res)
+	};
 
-	write!(&mut out_buf, "word,")?;
+	// Inputs are counted in parallel, `collect` keeps them in the order of the arguments.
+	let results: Vec<_> = inputs.par_iter().map(count_input).collect();
 
-	if display_total {
-		write!(&mut out_buf, "{},", total_label)?;
+	let mut counts = Vec::new();
+
+	for (c, res) in results {
+		counts.extend(c);
+
+		if werror && res.is_none() {
+			break;
+		}
+	}
+
+	counts
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fs;
+
+	use ustr::ustr;
+
+	use super::*;
+
+	/// Writes the tar fixture of the `archive` tests into `dir`.
+	fn write_tar(dir: &std::path::Path) -> String {
+		let path = dir.join("test.tar");
+		fs::write(&path, archive::tests::tar_fixture()).unwrap();
+
+		path.to_string_lossy().into_owned()
 	}
 
-	for label in counts.iter().map(|r| r.label()) {
-		write!(&mut out_buf, "{},", label)?;
+	#[test]
+	fn archive_members() {
+		let dir = tempfile::tempdir().unwrap();
+		let tar = write_tar(dir.path());
+
+		let counts = get_counts(
+			&[Input::Path(tar.clone())],
+			&CountOptions::default(),
+			false,
+			false,
+		)
+		.unwrap();
+
+		let labels: Vec<_> = counts.iter().map(|c| c.label()).collect();
+		assert_eq!(
+			labels,
+			[format!("{}:dir/a.txt", tar), format!("{}:b.txt", tar)]
+		);
+		assert_eq!(counts[0].counts[&ustr("ipsum")], 1);
+		assert_eq!(counts[1].counts[&ustr("dolor")], 1);
 	}
-	// Last ',' is redundant.
-	out_buf.pop().context("No ',' at the end")?;
-	out_buf.push('\n');
 
-	io::Write::write(&mut writer, out_buf.as_bytes())?;
-	for (word, count) in words_to_print {
-		out_buf.clear();
+	#[test]
+	fn aggregate_archives() {
+		let dir = tempfile::tempdir().unwrap();
+		let tar = write_tar(dir.path());
+
+		let counts = get_counts(
+			&[Input::Path(tar.clone())],
+			&CountOptions::default(),
+			true,
+			false,
+		)
+		.unwrap();
+
+		assert_eq!(counts.len(), 1);
+		assert_eq!(counts[0].label(), tar);
+		assert_eq!(counts[0].counts[&ustr("lorem")], 1);
+		assert_eq!(counts[0].counts[&ustr("ipsum")], 1);
+		assert_eq!(counts[0].counts[&ustr("dolor")], 1);
+		assert_eq!(counts[0].counts.len(), 3);
+	}
 
-		write!(&mut out_buf, "{},", word.as_str())?;
+	/// Writes the files with the given contents into `dir`, and returns their paths.
+	fn write_files(dir: &std::path::Path, contents: &[String]) -> Vec<String> {
+		contents.iter()
+			.enumerate()
+			.map(|(i, content)| {
+				let path = dir.join(format!("{}.txt", i));
+				fs::write(&path, content).unwrap();
 
-		if display_total {
-			write!(&mut out_buf, "{},", count)?;
-		}
+				path.to_string_lossy().into_owned()
+			})
+			.collect()
+	}
 
-		// Can't just use `write_record`, as the closures didn't play well with the buffer.
-		for c in counts.iter() {
-			write!(&mut out_buf, "{},", c.count(&word))?;
-		}
-		out_buf.pop().context("No ',' at the end")?;
-		out_buf.push('\n');
+	#[test]
+	fn input_order() {
+		let dir = tempfile::tempdir().unwrap();
+		// First input is the largest, so the others finish before it.
+		let contents = [
+			"lorem ipsum ".repeat(200_000),
+			String::from("lorem"),
+			String::from("ipsum"),
+			String::from("dolor"),
+		];
+		let files = write_files(dir.path(), &contents);
+		let inputs: Vec<_> = files.iter().cloned().map(Input::Path).collect();
+
+		let pool = ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+		let counts = pool
+			.install(|| get_counts(&inputs, &CountOptions::default(), false, false))
+			.unwrap();
+
+		let labels: Vec<_> = counts.iter().map(|c| c.label()).collect();
+		assert_eq!(labels, files);
+		assert_eq!(counts[0].counts[&ustr("lorem")], 200_000);
+	}
 
-		io::Write::write(&mut writer, out_buf.as_bytes())?;
+	#[test]
+	fn werror_missing_input() {
+		let dir = tempfile::tempdir().unwrap();
+		let files =
+			write_files(dir.path(), &[String::from("lorem"), String::from("ipsum")]);
+		let missing = dir
+			.path()
+			.join("missing.txt")
+			.to_string_lossy()
+			.into_owned();
+
+		let inputs = [
+			Input::Path(files[0].clone()),
+			Input::Path(missing),
+			Input::Path(files[1].clone()),
+		];
+		let opts = CountOptions::default();
+
+		// The global warning flag is not used, as the other tests may set it.
+		let labels = |werror: bool| -> Vec<String> {
+			count_inputs(&inputs, &opts, false, werror, &|| false)
+				.iter()
+				.map(|c| c.label().to_string())
+				.collect()
+		};
+
+		// Inputs after the missing one are not counted with `--werror`.
+		assert_eq!(labels(false), [files[0].clone(), files[1].clone()]);
+		assert_eq!(labels(true), [files[0].clone()]);
 	}
 
-	Ok(())
+	#[test]
+	fn werror_after_warning() {
+		let dir = tempfile::tempdir().unwrap();
+		let files = write_files(dir.path(), &[String::from("lorem")]);
+		let inputs = [Input::Path(files[0].clone())];
+		let opts = CountOptions::default();
+
+		// Inputs that are not started yet are skipped after a warning, only with `--werror`.
+		assert!(count_inputs(&inputs, &opts, false, true, &|| true).is_empty());
+		assert_eq!(
+			count_inputs(&inputs, &opts, false, false, &|| true).len(),
+			1
+		);
+	}
 }
@@ -0,0 +1,152 @@
+use std::borrow::Cow;
+
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+use crate::args::{CaseLocale, NormalForm};
+
+/// How the case of the text is mapped before counting.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CaseMode {
+	/// Case is kept as is.
+	#[default]
+	Preserve,
+	/// Text is lower cased.
+	Lower,
+	/// Text is case folded with the full Unicode case folding, so `ß` matches `SS`.
+	Fold,
+}
+
+/// Maps the text to the form that is counted, so the different ways of writing a word are
+/// counted together.
+///
+/// Applied to both the counted text and the word lists, such as `--excluded-words`, so they
+/// still match.
+#[derive(Debug, Clone, Default)]
+pub struct Normalizer {
+	pub case: CaseMode,
+	/// Locale of the case mapping, Unicode default case mapping is used if not set.
+	pub locale: Option<CaseLocale>,
+	/// Remove the diacritics, by decomposing the text and removing the combining marks.
+	pub strip_diacritics: bool,
+	/// Unicode normalization form, applied after the case mapping and the diacritic removal.
+	pub form: Option<NormalForm>,
+}
+
+impl Normalizer {
+	/// Returns `s` in the form that is counted. Borrows `s` if there is nothing to do.
+	pub fn normalize<'a>(&self, s: &'a str) -> Cow<'a, str> {
+		let mut res = Cow::Borrowed(s);
+
+		if self.case != CaseMode::Preserve {
+			if let Some(CaseLocale::Tr | CaseLocale::Az) = self.locale {
+				// Turkish and Azerbaijani have dotted and dotless i, as seperate letters.
+				// `İ` may also be decomposed, as `I` followed by U+0307.
+				res = Cow::Owned(
+					res.replace("I\u{307}", "i")
+						.replace('İ', "i")
+						.replace('I', "ı"),
+				);
+			}
+
+			res = match self.case {
+				CaseMode::Lower => Cow::Owned(res.to_lowercase()),
+				CaseMode::Fold => Cow::Owned(caseless::default_case_fold_str(&res)),
+				CaseMode::Preserve => res,
+			};
+		}
+
+		if self.strip_diacritics {
+			// Composed again, as not every decomposed character has a combining mark.
+			res = Cow::Owned(
+				res.nfd().filter(|c| !is_combining_mark(*c)).nfc().collect(),
+			);
+		}
+
+		match self.form {
+			None => res,
+			Some(NormalForm::Nfc) => Cow::Owned(res.nfc().collect()),
+			Some(NormalForm::Nfkc) => Cow::Owned(res.nfkc().collect()),
+			Some(NormalForm::Nfd) => Cow::Owned(res.nfd().collect()),
+			Some(NormalForm::Nfkd) => Cow::Owned(res.nfkd().collect()),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn case_fold() {
+		let lower = Normalizer {
+			case: CaseMode::Lower,
+			..Default::default()
+		};
+		let fold = Normalizer {
+			case: CaseMode::Fold,
+			..Default::default()
+		};
+
+		assert_eq!(lower.normalize("Straße STRASSE"), "straße strasse");
+		assert_eq!(fold.normalize("Straße STRASSE"), "strasse strasse");
+	}
+
+	#[test]
+	fn turkish() {
+		let normalizer = Normalizer {
+			case: CaseMode::Lower,
+			locale: Some(CaseLocale::Tr),
+			..Default::default()
+		};
+
+		assert_eq!(
+			normalizer.normalize("IŞIK İzmir I\u{307}ki"),
+			"ışık izmir iki"
+		);
+		assert_eq!(
+			Normalizer {
+				case: CaseMode::Lower,
+				..Default::default()
+			}
+			.normalize("IŞIK"),
+			"işik"
+		);
+	}
+
+	#[test]
+	fn normal_form() {
+		let nfc = Normalizer {
+			form: Some(NormalForm::Nfc),
+			..Default::default()
+		};
+		let nfkd = Normalizer {
+			form: Some(NormalForm::Nfkd),
+			..Default::default()
+		};
+
+		assert_eq!(nfc.normalize("cafe\u{301}"), "café");
+		assert_eq!(nfkd.normalize("café ﬁ"), "cafe\u{301} fi");
+	}
+
+	#[test]
+	fn strip_diacritics() {
+		let normalizer = Normalizer {
+			case: CaseMode::Lower,
+			strip_diacritics: true,
+			..Default::default()
+		};
+
+		assert_eq!(
+			normalizer.normalize("Café cafe\u{301} naïve Ömür 한국"),
+			"cafe cafe naive omur 한국"
+		);
+	}
+
+	#[test]
+	fn borrowed() {
+		assert!(matches!(
+			Normalizer::default().normalize("lorem"),
+			Cow::Borrowed(_)
+		));
+	}
+}
@@ -0,0 +1,649 @@
+use std::fmt::Write as _;
+use std::io::Write;
+
+use anyhow::Result;
+use serde::Serialize;
+use unicode_width::UnicodeWidthStr;
+use ustr::Ustr;
+
+use crate::{
+	args::{LineEnding, OutputFormat},
+	count::{StreamWordCount, TotalCount},
+};
+
+/// Version of the JSON and NDJSON schema. Incremented only if a field is removed or its meaning
+/// is changed, new fields may be added without it.
+const SCHEMA_VERSION: u32 = 1;
+
+/// Options of the CSV output.
+#[derive(Debug, Clone)]
+pub struct CsvOptions {
+	pub delimiter: char,
+	pub line_ending: LineEnding,
+	/// Start the output with a UTF-8 byte order mark.
+	pub bom: bool,
+}
+
+impl Default for CsvOptions {
+	fn default() -> Self {
+		CsvOptions {
+			delimiter: ',',
+			line_ending: LineEnding::Lf,
+			bom: false,
+		}
+	}
+}
+
+/// Width of the output if it is not a terminal.
+const DEFAULT_WIDTH: usize = 80;
+
+/// Minimum width of the bars of the histogram.
+const MIN_BAR_WIDTH: usize = 10;
+
+/// Blocks that are an eighth to seven eighths of a character wide, for the end of the bars.
+const PARTIAL_BLOCKS: [char; 7] = ['▏', '▎', '▍', '▌', '▋', '▊', '▉'];
+
+/// Results of the counting, as they are written to the output.
+pub struct Report<'a> {
+	/// Counts of every stream, one column each.
+	pub counts: &'a [StreamWordCount],
+	pub total: &'a TotalCount,
+	/// Words and their total counts, in the order they are displayed.
+	pub rows: &'a [(Ustr, usize)],
+	/// Display the total count column. Only applies to the tabular formats.
+	pub display_total: bool,
+	pub total_label: &'a str,
+	pub csv: CsvOptions,
+}
+
+/// Metadata of a stream, or the total, in the JSON output.
+#[derive(Serialize)]
+struct JsonStream<'a> {
+	label: &'a str,
+	/// Sum of the counts of every word.
+	total: usize,
+	/// Number of the distinct words.
+	unique: usize,
+}
+
+/// A word row in the JSON output.
+#[derive(Serialize)]
+struct JsonRow<'a> {
+	word: &'a str,
+	total: usize,
+	/// Counts in the order of `streams`.
+	counts: Vec<usize>,
+}
+
+#[derive(Serialize)]
+struct Json<'a> {
+	schema_version: u32,
+	streams: Vec<JsonStream<'a>>,
+	total: JsonStream<'a>,
+	rows: Vec<JsonRow<'a>>,
+}
+
+/// Count of a word in a stream, in the NDJSON output.
+#[derive(Serialize)]
+struct NdjsonCount<'a> {
+	label: &'a str,
+	count: usize,
+}
+
+/// A line of the NDJSON output.
+#[derive(Serialize)]
+struct NdjsonRow<'a> {
+	schema_version: u32,
+	word: &'a str,
+	total: usize,
+	counts: Vec<NdjsonCount<'a>>,
+}
+
+impl Report<'_> {
+	/// Writes the report to `writer` in the given format.
+	pub fn write<W: Write>(&self, format: OutputFormat, writer: &mut W) -> Result<()> {
+		match format {
+			OutputFormat::Csv => self.write_csv(writer)?,
+			OutputFormat::Json => self.write_json(writer)?,
+			OutputFormat::Ndjson => self.write_ndjson(writer)?,
+			OutputFormat::Table => self.write_table(writer, false)?,
+			OutputFormat::Markdown => self.write_table(writer, true)?,
+			OutputFormat::Histogram => {
+				let width = terminal_size::terminal_size()
+					.map_or(DEFAULT_WIDTH, |(w, _)| w.0 as usize);
+
+				self.write_histogram(writer, width)?
+			}
+		}
+
+		writer.flush()?;
+
+		Ok(())
+	}
+
+	/// Writes the report as CSV, quoting the fields as described in RFC 4180.
+	fn write_csv<W: Write>(&self, writer: &mut W) -> Result<()> {
+		let line_ending = match self.csv.line_ending {
+			LineEnding::Lf => "\n",
+			LineEnding::Crlf => "\r\n",
+		};
+		// `out_buf` is used to avoid unnecessary allocations caused by the `to_string`
+		// method of the numeric types. The line is formatted here before pushing to writer.
+		let mut out_buf = String::new();
+
+		if self.csv.bom {
+			out_buf.push('\u{feff}');
+		}
+
+		self.push_field(&mut out_buf, "word");
+
+		if self.display_total {
+			out_buf.push(self.csv.delimiter);
+			self.push_field(&mut out_buf, self.total_label);
+		}
+
+		for label in self.counts.iter().map(|r| r.label()) {
+			out_buf.push(self.csv.delimiter);
+			self.push_field(&mut out_buf, label);
+		}
+		out_buf.push_str(line_ending);
+
+		writer.write_all(out_buf.as_bytes())?;
+		for (word, count) in self.rows {
+			out_buf.clear();
+
+			self.push_field(&mut out_buf, word);
+
+			if self.display_total {
+				write!(&mut out_buf, "{}{}", self.csv.delimiter, count)?;
+			}
+
+			for c in self.counts.iter() {
+				write!(&mut out_buf, "{}{}", self.csv.delimiter, c.count(word))?;
+			}
+			out_buf.push_str(line_ending);
+
+			writer.write_all(out_buf.as_bytes())?;
+		}
+
+		Ok(())
+	}
+
+	/// Pushes `field` to `buf`. Fields with a delimiter, a quote or a line break are quoted, and
+	/// the quotes in them are doubled.
+	fn push_field(&self, buf: &mut String, field: &str) {
+		if field.contains([self.csv.delimiter, '"', '\r', '\n']) {
+			buf.push('"');
+			buf.push_str(&field.replace('"', "\"\""));
+			buf.push('"');
+		} else {
+			buf.push_str(field);
+		}
+	}
+
+	/// Writes the report as a table with aligned columns, or as a GitHub Markdown pipe table if
+	/// `markdown` is set.
+	///
+	/// Columns are aligned by the display width of the cells, so wide characters, such as CJK, and
+	/// combining marks don't break the alignment in the terminal.
+	fn write_table<W: Write>(&self, writer: &mut W, markdown: bool) -> Result<()> {
+		let escape = |s: &str| -> String {
+			match markdown {
+				true => escape_control(s).replace('|', "\\|"),
+				false => escape_control(s),
+			}
+		};
+
+		let mut header = vec![String::from("word")];
+		if self.display_total {
+			header.push(escape(self.total_label));
+		}
+		header.extend(self.counts.iter().map(|c| escape(c.label())));
+
+		let rows: Vec<Vec<String>> = self
+			.rows
+			.iter()
+			.map(|(word, count)| {
+				let mut row = vec![escape(word)];
+				if self.display_total {
+					row.push(count.to_string());
+				}
+				row.extend(self.counts.iter().map(|c| c.count(word).to_string()));
+
+				row
+			})
+			.collect();
+
+		// Markdown needs at least 3 characters for the delimiter row.
+		let min_width = if markdown { 3 } else { 0 };
+		let widths: Vec<usize> = (0..header.len())
+			.map(|i| {
+				std::iter::once(&header)
+					.chain(rows.iter())
+					.map(|r| r[i].width())
+					.fold(min_width, usize::max)
+			})
+			.collect();
+
+		let write_row = |writer: &mut W, row: &[String]| -> Result<()> {
+			let mut line = String::new();
+
+			for (i, (cell, width)) in row.iter().zip(widths.iter()).enumerate() {
+				let pad = " ".repeat(width - cell.width());
+
+				if markdown {
+					line.push_str("| ");
+				} else if i > 0 {
+					line.push_str("  ");
+				}
+
+				// Words are aligned to the left, counts to the right.
+				match i {
+					0 => write!(line, "{}{}", cell, pad)?,
+					_ => write!(line, "{}{}", pad, cell)?,
+				}
+
+				if markdown {
+					line.push(' ');
+				}
+			}
+
+			if markdown {
+				line.push('|');
+			}
+
+			writeln!(writer, "{}", line.trim_end())?;
+			Ok(())
+		};
+
+		write_row(writer, &header)?;
+
+		let delimiter_row: Vec<String> = widths
+			.iter()
+			.enumerate()
+			.map(|(i, w)| match (markdown, i) {
+				(true, 0) => format!(":{}", "-".repeat(w - 1)),
+				(true, _) => format!("{}:", "-".repeat(w - 1)),
+				(false, _) => "-".repeat(*w),
+			})
+			.collect();
+		write_row(writer, &delimiter_row)?;
+
+		for row in rows.iter() {
+			write_row(writer, row)?;
+		}
+
+		Ok(())
+	}
+
+	/// Writes the rows as horizontal bars, scaled to fit in `width` columns, with their counts and
+	/// percentages.
+	///
+	/// If there is more than one stream, or the total is displayed, every row is a group of bars,
+	/// one per stream. Percentages are of the sum of the counts of the stream.
+	fn write_histogram<W: Write>(&self, writer: &mut W, width: usize) -> Result<()> {
+		// Label, counts and the sum of the counts of every bar in a group.
+		let mut series: Vec<(String, Vec<usize>, usize)> = Vec::new();
+
+		if self.display_total {
+			series.push((
+				escape_control(self.total_label),
+				self.rows.iter().map(|(_, c)| *c).collect(),
+				self.total.counts.values().sum(),
+			));
+		}
+
+		for c in self.counts.iter() {
+			series.push((
+				escape_control(c.label()),
+				self.rows.iter().map(|(w, _)| c.count(w)).collect(),
+				c.counts.values().sum(),
+			));
+		}
+
+		let grouped = series.len() > 1;
+		let words: Vec<String> = self.rows.iter().map(|(w, _)| escape_control(w)).collect();
+
+		let word_width = words.iter().map(|w| w.width()).max().unwrap_or(0);
+		let label_width = match grouped {
+			true => series.iter().map(|(l, _, _)| l.width()).max().unwrap_or(0) + 2,
+			false => 0,
+		};
+		let max = series
+			.iter()
+			.flat_map(|(_, c, _)| c)
+			.copied()
+			.max()
+			.unwrap_or(0);
+		let count_width = max.to_string().len();
+		// Percentages are at most `100.0%`.
+		let percent_width = 6;
+
+		let bar_width = width
+			.saturating_sub(word_width + label_width + count_width + percent_width + 6)
+			.max(MIN_BAR_WIDTH);
+
+		for (i, word) in words.iter().enumerate() {
+			if grouped && i > 0 {
+				writeln!(writer)?;
+			}
+
+			for (j, (label, counts, sum)) in series.iter().enumerate() {
+				let count = counts[i];
+				let percent = match sum {
+					0 => 0.0,
+					_ => count as f64 * 100.0 / *sum as f64,
+				};
+
+				// Word is only written for the first bar of the group.
+				let word = if j == 0 { word.as_str() } else { "" };
+				let mut line = format!(
+					"{}{}  ",
+					word,
+					" ".repeat(word_width - word.width())
+				);
+
+				if grouped {
+					write!(
+						line,
+						"{}{}",
+						label,
+						" ".repeat(label_width - label.width())
+					)?;
+				}
+
+				let bar = bar(count, max, bar_width);
+				write!(
+					line,
+					"{}{}  {:>count_width$}  {:>percent_width$}",
+					bar,
+					" ".repeat(bar_width - bar.chars().count()),
+					count,
+					format!("{:.1}%", percent),
+				)?;
+
+				writeln!(writer, "{}", line)?;
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Writes a single JSON object, see the README for the schema.
+	fn write_json<W: Write>(&self, writer: &mut W) -> Result<()> {
+		let json = Json {
+			schema_version: SCHEMA_VERSION,
+			streams: self
+				.counts
+				.iter()
+				.map(|c| JsonStream {
+					label: c.label(),
+					total: c.counts.values().sum(),
+					unique: c.counts.len(),
+				})
+				.collect(),
+			total: JsonStream {
+				label: self.total_label,
+				total: self.total.counts.values().sum(),
+				unique: self.total.counts.values().filter(|c| **c > 0).count(),
+			},
+			rows: self
+				.rows
+				.iter()
+				.map(|(word, count)| JsonRow {
+					word: word.as_str(),
+					total: *count,
+					counts: self.counts.iter().map(|c| c.count(word)).collect(),
+				})
+				.collect(),
+		};
+
+		serde_json::to_writer(&mut *writer, &json)?;
+		writeln!(writer)?;
+
+		Ok(())
+	}
+
+	/// Writes a JSON object per word, one per line, see the README for the schema.
+	fn write_ndjson<W: Write>(&self, writer: &mut W) -> Result<()> {
+		for (word, count) in self.rows {
+			let row = NdjsonRow {
+				schema_version: SCHEMA_VERSION,
+				word: word.as_str(),
+				total: *count,
+				counts: self
+					.counts
+					.iter()
+					.map(|c| NdjsonCount {
+						label: c.label(),
+						count: c.count(word),
+					})
+					.collect(),
+			};
+
+			serde_json::to_writer(&mut *writer, &row)?;
+			writeln!(writer)?;
+		}
+
+		Ok(())
+	}
+}
+
+/// Returns `s` with the control characters, such as line breaks, escaped.
+fn escape_control(s: &str) -> String {
+	s.chars()
+		.map(|c| match c.is_control() {
+			true => c.escape_debug().to_string(),
+			false => c.to_string(),
+		})
+		.collect()
+}
+
+/// Returns a bar of `value` in a chart of `width` columns, that `max` fills completely.
+fn bar(value: usize, max: usize, width: usize) -> String {
+	// Length of the bar in eighths of a column.
+	let eighths = match max {
+		0 => 0,
+		_ => value * width * 8 / max,
+	};
+
+	let mut bar = "█".repeat(eighths / 8);
+	if eighths % 8 > 0 {
+		bar.push(PARTIAL_BLOCKS[eighths % 8 - 1]);
+	}
+
+	bar
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use ustr::ustr;
+
+	fn write(format: OutputFormat, display_total: bool) -> String {
+		write_with(format, display_total, "a.txt", CsvOptions::default())
+	}
+
+	fn write_with(
+		format: OutputFormat,
+		display_total: bool,
+		label: &str,
+		csv: CsvOptions,
+	) -> String {
+		let mut a = StreamWordCount::new(String::from(label));
+		a.counts.insert(ustr("lorem"), 2);
+		a.counts.insert(ustr("ipsum"), 1);
+		let mut b = StreamWordCount::new(String::from("b.txt"));
+		b.counts.insert(ustr("lorem"), 1);
+
+		let counts = [a, b];
+		let total = TotalCount::from_counts(counts.iter());
+		let rows = total.to_ordered_vec();
+
+		let report = Report {
+			counts: &counts,
+			total: &total,
+			rows: &rows,
+			display_total,
+			total_label: "total_count",
+			csv,
+		};
+
+		let mut res = Vec::new();
+		report.write(format, &mut res).unwrap();
+
+		String::from_utf8(res).unwrap()
+	}
+
+	#[test]
+	fn csv() {
+		assert_eq!(
+			write(OutputFormat::Csv, true),
+			"word,total_count,a.txt,b.txt\nlorem,3,2,1\nipsum,1,1,0\n"
+		);
+		assert_eq!(
+			write(OutputFormat::Csv, false),
+			"word,a.txt,b.txt\nlorem,2,1\nipsum,1,0\n"
+		);
+	}
+
+	#[test]
+	fn csv_quoting() {
+		assert_eq!(
+			write_with(
+				OutputFormat::Csv,
+				false,
+				"my \"a\", b.txt",
+				CsvOptions::default()
+			),
+			"word,\"my \"\"a\"\", b.txt\",b.txt\nlorem,2,1\nipsum,1,0\n"
+		);
+	}
+
+	#[test]
+	fn csv_options() {
+		let csv = CsvOptions {
+			delimiter: ';',
+			line_ending: LineEnding::Crlf,
+			bom: true,
+		};
+
+		assert_eq!(
+			write_with(OutputFormat::Csv, true, "a;b.txt", csv),
+			"\u{feff}word;total_count;\"a;b.txt\";b.txt\r\nlorem;3;2;1\r\nipsum;1;1;0\r\n"
+		);
+	}
+
+	#[test]
+	fn table() {
+		assert_eq!(
+			write_with(OutputFormat::Table, true, "式.txt", CsvOptions::default()),
+			concat!(
+				"word   total_count  式.txt  b.txt\n",
+				"-----  -----------  ------  -----\n",
+				"lorem            3       2      1\n",
+				"ipsum            1       1      0\n",
+			)
+		);
+	}
+
+	#[test]
+	fn markdown() {
+		assert_eq!(
+			write_with(
+				OutputFormat::Markdown,
+				false,
+				"a|b.txt",
+				CsvOptions::default()
+			),
+			concat!(
+				"| word  | a\\|b.txt | b.txt |\n",
+				"| :---- | -------: | ----: |\n",
+				"| lorem |        2 |     1 |\n",
+				"| ipsum |        1 |     0 |\n",
+			)
+		);
+	}
+
+	#[test]
+	fn bars() {
+		assert_eq!(bar(4, 4, 10), "██████████");
+		assert_eq!(bar(1, 4, 10), "██▌");
+		assert_eq!(bar(0, 4, 10), "");
+		assert_eq!(bar(0, 0, 10), "");
+	}
+
+	#[test]
+	fn histogram() {
+		let mut a = StreamWordCount::new(String::from("a.txt"));
+		a.counts.insert(ustr("lorem"), 3);
+		a.counts.insert(ustr("ipsum"), 1);
+
+		let counts = [a];
+		let total = TotalCount::from_counts(counts.iter());
+		let rows = total.to_ordered_vec();
+
+		let report = Report {
+			counts: &counts,
+			total: &total,
+			rows: &rows,
+			display_total: false,
+			total_label: "total_count",
+			csv: CsvOptions::default(),
+		};
+
+		let mut res = Vec::new();
+		report.write_histogram(&mut res, 40).unwrap();
+
+		assert_eq!(
+			String::from_utf8(res).unwrap(),
+			concat!(
+				"lorem  ██████████████████████  3   75.0%\n",
+				"ipsum  ███████▎                1   25.0%\n",
+			)
+		);
+	}
+
+	#[test]
+	fn histogram_grouped() {
+		let res = write(OutputFormat::Histogram, true);
+		let lines: Vec<&str> = res.lines().collect();
+
+		assert_eq!(lines.len(), 7);
+		assert!(lines[0].starts_with("lorem  total_count  "));
+		assert!(lines[0].ends_with("3   75.0%"));
+		assert!(lines[1].starts_with("       a.txt        "));
+		assert!(lines[1].ends_with("2   66.7%"));
+		assert!(lines[2].ends_with("1  100.0%"));
+		assert_eq!(lines[3], "");
+		assert!(lines[4].starts_with("ipsum  total_count  "));
+		assert!(lines[6].ends_with("0    0.0%"));
+	}
+
+	#[test]
+	fn json() {
+		assert_eq!(
+			write(OutputFormat::Json, false),
+			concat!(
+				r#"{"schema_version":1,"#,
+				r#""streams":[{"label":"a.txt","total":3,"unique":2},{"label":"b.txt","total":1,"unique":1}],"#,
+				r#""total":{"label":"total_count","total":4,"unique":2},"#,
+				r#""rows":[{"word":"lorem","total":3,"counts":[2,1]},{"word":"ipsum","total":1,"counts":[1,0]}]}"#,
+				"\n"
+			)
+		);
+	}
+
+	#[test]
+	fn ndjson() {
+		assert_eq!(
+			write(OutputFormat::Ndjson, true),
+			concat!(
+				r#"{"schema_version":1,"word":"lorem","total":3,"counts":[{"label":"a.txt","count":2},{"label":"b.txt","count":1}]}"#,
+				"\n",
+				r#"{"schema_version":1,"word":"ipsum","total":1,"counts":[{"label":"a.txt","count":1},{"label":"b.txt","count":0}]}"#,
+				"\n"
+			)
+		);
+	}
+}
@@ -19,6 +19,16 @@ pub static NODASH_REGEX: LazyLock<Regex> =
 pub static NOAPOSTROPHE_REGEX: LazyLock<Regex> =
 	LazyLock::new(|| Regex::new(r"(\p{Alphabetic}|\d)(\p{Alphabetic}|\d|-)*").unwrap());
 
+/// Matches the runs of the scripts that are written without spaces between words.
+pub static SEGMENTABLE_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+	Regex::new(r"[\p{Han}\p{Hiragana}\p{Katakana}\p{Thai}\p{Lao}\p{Khmer}\p{Myanmar}ー]+")
+		.unwrap()
+});
+
+/// Matches the strings that start with a punctuation or a symbol.
+pub static PUNCTUATION_REGEX: LazyLock<Regex> =
+	LazyLock::new(|| Regex::new(r"^[\p{P}\p{S}]").unwrap());
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -372,4 +382,14 @@ mod tests {
 
 		assert_eq!(rres, vec!["lorem", "ip-sum", "dol3", "or"]);
 	}
+
+	#[test]
+	fn segmentable1() {
+		let rres: Vec<_> = SEGMENTABLE_REGEX
+			.find_iter("lorem 東京ですカレー ipsum สวัสดีครับ 2d3")
+			.map(|m| m.as_str())
+			.collect();
+
+		assert_eq!(rres, vec!["東京ですカレー", "สวัสดีครับ"]);
+	}
 }
@@ -0,0 +1,31 @@
+use crate::args::StopwordLanguage;
+
+/// Returns the bundled stop-word list of the language, one word per line.
+pub fn stopwords(lang: StopwordLanguage) -> &'static str {
+	match lang {
+		StopwordLanguage::De => include_str!("stopwords/de.txt"),
+		StopwordLanguage::En => include_str!("stopwords/en.txt"),
+		StopwordLanguage::Es => include_str!("stopwords/es.txt"),
+		StopwordLanguage::Fr => include_str!("stopwords/fr.txt"),
+		StopwordLanguage::It => include_str!("stopwords/it.txt"),
+		StopwordLanguage::Nl => include_str!("stopwords/nl.txt"),
+		StopwordLanguage::Pt => include_str!("stopwords/pt.txt"),
+		StopwordLanguage::Tr => include_str!("stopwords/tr.txt"),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use clap::ValueEnum;
+
+	#[test]
+	fn lists() {
+		for lang in StopwordLanguage::value_variants() {
+			let list = stopwords(*lang);
+
+			assert!(!list.trim().is_empty());
+			assert!(list.lines().all(|w| !w.is_empty() && w == w.to_lowercase()));
+		}
+	}
+}
@@ -1,28 +1,77 @@
 use std::{
 	fs::{self, File},
-	io::{self, Read},
+	io::{self, BufRead, BufReader, ErrorKind, Read},
 };
 
-use crate::wprintln;
+use bzip2::bufread::MultiBzDecoder;
+use flate2::bufread::MultiGzDecoder;
+use xz2::bufread::XzDecoder;
+
+use crate::{args::InputEncoding, decode::TextDecoder, wprintln};
+
+/// Uncompressed stream that the compressed `Stream`s are decoded from.
+type Raw<'a> = Box<dyn BufRead + 'a>;
+
+/// Decoder for zstd compressed streams.
+type ZstdDecoder<'a> = zstd::stream::read::Decoder<'static, Raw<'a>>;
+
+/// Number of bytes that are read from a stream at once.
+const CHUNK_SIZE: usize = 64 * 1024;
+/// Maximum number of bytes that are carried to the next chunk, while looking for a whitespace.
+const MAX_CARRY: usize = 1024 * 1024;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const BZIP2_MAGIC: &[u8] = b"BZh";
+const XZ_MAGIC: &[u8] = &[0xfd, b'7', b'z', b'X', b'Z', 0x00];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
 
 /// Represents a stream of string, either from a file or from Stdin.
-#[derive(Debug)]
-pub enum Stream {
+///
+/// Compressed streams are detected by their magic bytes and decompressed transparently.
+///
+/// Archive members borrow the reader of their archive, that is what the lifetime is for. Other
+/// streams are `'static`.
+pub enum Stream<'a> {
 	/// Represents standard input.
-	Stdin(io::Stdin),
+	Stdin(BufReader<io::Stdin>),
 	/// Represents a file.
 	///
 	/// First element is a `File`, the second is the path to the file.
-	File(File, String),
+	File(BufReader<File>, String),
+	/// Represents a member of an archive.
+	///
+	/// First element is the reader of the member, the second is its label, in the form of
+	/// `archive:path/in/archive`.
+	Member(Raw<'a>, String),
+	/// Represents a gzip compressed file or standard input.
+	///
+	/// First element is the decoder, the second is the path to the file, `-` for Stdin.
+	Gzip(Box<BufReader<MultiGzDecoder<Raw<'a>>>>, String),
+	/// Represents a bzip2 compressed file or standard input.
+	///
+	/// First element is the decoder, the second is the path to the file, `-` for Stdin.
+	Bzip2(Box<BufReader<MultiBzDecoder<Raw<'a>>>>, String),
+	/// Represents a xz compressed file or standard input.
+	///
+	/// First element is the decoder, the second is the path to the file, `-` for Stdin.
+	Xz(Box<BufReader<XzDecoder<Raw<'a>>>>, String),
+	/// Represents a zstd compressed file or standard input.
+	///
+	/// First element is the decoder, the second is the path to the file, `-` for Stdin.
+	Zstd(Box<BufReader<ZstdDecoder<'a>>>, String),
 }
 
-impl Stream {
+impl Stream<'static> {
 	/// Creates a `Stream` from the given string.
 	///
 	/// If the string is equal to `-`, then it is Stdin. If not, then it will be considered as a path to a file.
-	pub fn from_str(path: &str) -> Option<Stream> {
+	pub fn from_str(path: &str) -> Option<Self> {
 		if path == "-" {
-			return Some(Stream::Stdin(io::stdin()));
+			return Self::decompressed(
+				BufReader::new(io::stdin()),
+				path,
+				Stream::Stdin,
+			);
 		}
 
 		let meta = match fs::metadata(path) {
@@ -35,7 +84,9 @@ impl Stream {
 
 		if meta.is_file() {
 			match File::open(path) {
-				Ok(file) => Some(Stream::File(file, path.to_string())),
+				Ok(file) => Self::decompressed(BufReader::new(file), path, |f| {
+					Stream::File(f, path.to_string())
+				}),
 				Err(e) => {
 					wprintln!("{}: {}", path, e);
 					None
@@ -49,37 +100,272 @@ impl Stream {
 			None
 		}
 	}
+}
+
+impl<'a> Stream<'a> {
+	/// Creates a `Stream` from the reader of an archive member.
+	pub fn from_member<R>(reader: R, label: String) -> Option<Stream<'a>>
+	where
+		R: BufRead + 'a,
+	{
+		Self::decompressed(reader, &label, |r| {
+			Stream::Member(Box::new(r), label.clone())
+		})
+	}
 
-	/// Reads the `Stream` and returns its contents as a `String`.
+	/// Checks the magic bytes of `reader` and wraps it with the matching decoder.
 	///
-	/// Can't read invalid UTF-8 content.
-	pub fn read_to_string(&mut self, buf: &mut String) -> Option<()> {
-		match self {
-			Stream::Stdin(si) => {
-				if let Err(e) = si.read_to_string(buf) {
-					wprintln!("-: {:#}", e);
-					return None;
-				}
+	/// If the stream is not compressed, `plain` is used to create the `Stream`.
+	fn decompressed<R>(
+		mut reader: R,
+		path: &str,
+		plain: impl FnOnce(R) -> Stream<'a>,
+	) -> Option<Stream<'a>>
+	where
+		R: BufRead + 'a,
+	{
+		let head = match reader.fill_buf() {
+			Ok(head) => head,
+			Err(e) => {
+				wprintln!("{}: {}", path, e);
+				return None;
 			}
-			Stream::File(f, n) => {
-				if let Err(e) = f.read_to_string(buf) {
-					wprintln!("{}: {:#}", n, e);
+		};
+
+		let path = path.to_string();
+
+		let stream = if head.starts_with(GZIP_MAGIC) {
+			let d = MultiGzDecoder::new(Box::new(reader) as Raw<'a>);
+			Stream::Gzip(Box::new(BufReader::new(d)), path)
+		} else if head.starts_with(BZIP2_MAGIC) {
+			let d = MultiBzDecoder::new(Box::new(reader) as Raw<'a>);
+			Stream::Bzip2(Box::new(BufReader::new(d)), path)
+		} else if head.starts_with(XZ_MAGIC) {
+			let d = XzDecoder::new_multi_decoder(Box::new(reader) as Raw<'a>);
+			Stream::Xz(Box::new(BufReader::new(d)), path)
+		} else if head.starts_with(ZSTD_MAGIC) {
+			match ZstdDecoder::with_buffer(Box::new(reader) as Raw<'a>) {
+				Ok(d) => Stream::Zstd(Box::new(BufReader::new(d)), path),
+				Err(e) => {
+					wprintln!("{}: {}", path, e);
 					return None;
 				}
 			}
+		} else {
+			plain(reader)
 		};
 
+		Some(stream)
+	}
+
+	/// Reads the `Stream`, decodes it with the `encoding` and appends its contents to `buf`.
+	///
+	/// Byte order marks are removed, as they are from the counted streams.
+	pub fn read_to_string(&mut self, encoding: InputEncoding, buf: &mut String) -> Option<()> {
+		self.read_chunks(encoding, |chunk| buf.push_str(chunk))
+	}
+
+	/// Reads the `Stream` in chunks, decodes them with the `encoding` and calls `f` with each of
+	/// them.
+	///
+	/// Chunks are split after a whitespace, so words are not split between them. If no whitespace
+	/// is found in `MAX_CARRY` bytes, the chunk is split at an arbitrary character boundary.
+	pub fn read_chunks<F>(&mut self, encoding: InputEncoding, f: F) -> Option<()>
+	where
+		F: FnMut(&str),
+	{
+		let mut decoder = TextDecoder::new(encoding);
+
+		if let Err(e) = self.try_read_chunks(&mut decoder, f) {
+			wprintln!("{}: {:#}", self.name(), e);
+			return None;
+		}
+
+		if decoder.replacements() > 0 {
+			eprintln!(
+				"{}: {} invalid sequences were replaced with U+FFFD",
+				self.name(),
+				decoder.replacements()
+			);
+		}
+
 		Some(())
 	}
 
+	fn try_read_chunks<F>(&mut self, decoder: &mut TextDecoder, mut f: F) -> io::Result<()>
+	where
+		F: FnMut(&str),
+	{
+		// Text that is decoded but not yet passed to `f`.
+		let mut pending = String::with_capacity(CHUNK_SIZE);
+		let mut read_buf = vec![0u8; CHUNK_SIZE];
+
+		loop {
+			let n = match self.read(&mut read_buf) {
+				Ok(n) => n,
+				Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+				Err(e) => return Err(e),
+			};
+
+			if n == 0 {
+				decoder.decode(&[], &mut pending, true)?;
+				f(&pending);
+				return Ok(());
+			}
+
+			decoder.decode(&read_buf[..n], &mut pending, false)?;
+
+			let end = match pending.rfind(char::is_whitespace) {
+				Some(i) => {
+					i + pending[i..].chars().next().map_or(0, char::len_utf8)
+				}
+				None if pending.len() >= MAX_CARRY => pending.len(),
+				None => continue,
+			};
+
+			f(&pending[..end]);
+			pending.drain(..end);
+		}
+	}
+
+	/// Returns the name of the `Stream` as it is given by the user.
+	///
+	/// Name is `-` for Stdin, and the path of file for the File.
+	#[inline]
+	pub fn name(&self) -> &str {
+		match self {
+			Self::Stdin(_) => "-",
+			Self::File(_, s)
+			| Self::Member(_, s)
+			| Self::Gzip(_, s)
+			| Self::Bzip2(_, s)
+			| Self::Xz(_, s)
+			| Self::Zstd(_, s) => s,
+		}
+	}
+
 	/// Returns the label of the `Stream`.
 	///
 	/// Label is `standard_input` for Stdin, and the path of file for the File.
 	#[inline]
 	pub fn label(&self) -> String {
+		match self.name() {
+			"-" => String::from("standard_input"),
+			s => s.to_string(),
+		}
+	}
+}
+
+impl Read for Stream<'_> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		match self {
+			Self::Stdin(r) => r.read(buf),
+			Self::File(r, _) => r.read(buf),
+			Self::Member(r, _) => r.read(buf),
+			Self::Gzip(r, _) => r.read(buf),
+			Self::Bzip2(r, _) => r.read(buf),
+			Self::Xz(r, _) => r.read(buf),
+			Self::Zstd(r, _) => r.read(buf),
+		}
+	}
+}
+
+impl BufRead for Stream<'_> {
+	fn fill_buf(&mut self) -> io::Result<&[u8]> {
 		match self {
-			Self::Stdin(_) => String::from("standard_input"),
-			Self::File(_, s) => s.clone(),
+			Self::Stdin(r) => r.fill_buf(),
+			Self::File(r, _) => r.fill_buf(),
+			Self::Member(r, _) => r.fill_buf(),
+			Self::Gzip(r, _) => r.fill_buf(),
+			Self::Bzip2(r, _) => r.fill_buf(),
+			Self::Xz(r, _) => r.fill_buf(),
+			Self::Zstd(r, _) => r.fill_buf(),
 		}
 	}
+
+	fn consume(&mut self, amt: usize) {
+		match self {
+			Self::Stdin(r) => r.consume(amt),
+			Self::File(r, _) => r.consume(amt),
+			Self::Member(r, _) => r.consume(amt),
+			Self::Gzip(r, _) => r.consume(amt),
+			Self::Bzip2(r, _) => r.consume(amt),
+			Self::Xz(r, _) => r.consume(amt),
+			Self::Zstd(r, _) => r.consume(amt),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::Write;
+
+	use super::*;
+
+	const TEXT: &str = "lorem ipsum dolor\nsit amet";
+
+	fn decompress<'a>(compressed: &'a [u8], label: &str) -> Stream<'a> {
+		let mut stream = Stream::from_member(compressed, label.to_string()).unwrap();
+
+		let mut content = String::new();
+		stream.read_to_string(InputEncoding::Utf8, &mut content)
+			.unwrap();
+
+		assert_eq!(content, TEXT);
+		assert_eq!(stream.label(), label);
+
+		stream
+	}
+
+	#[test]
+	fn gzip() {
+		let mut e =
+			flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+		e.write_all(TEXT.as_bytes()).unwrap();
+		let compressed = e.finish().unwrap();
+
+		assert!(matches!(
+			decompress(&compressed, "test.gz"),
+			Stream::Gzip(..)
+		));
+	}
+
+	#[test]
+	fn bzip2() {
+		let mut e = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+		e.write_all(TEXT.as_bytes()).unwrap();
+		let compressed = e.finish().unwrap();
+
+		assert!(matches!(
+			decompress(&compressed, "test.bz2"),
+			Stream::Bzip2(..)
+		));
+	}
+
+	#[test]
+	fn xz() {
+		let mut e = xz2::write::XzEncoder::new(Vec::new(), 6);
+		e.write_all(TEXT.as_bytes()).unwrap();
+		let compressed = e.finish().unwrap();
+
+		assert!(matches!(decompress(&compressed, "test.xz"), Stream::Xz(..)));
+	}
+
+	#[test]
+	fn zstd() {
+		let compressed = zstd::encode_all(TEXT.as_bytes(), 0).unwrap();
+
+		assert!(matches!(
+			decompress(&compressed, "test.zst"),
+			Stream::Zstd(..)
+		));
+	}
+
+	#[test]
+	fn plain() {
+		assert!(matches!(
+			decompress(TEXT.as_bytes(), "test.txt"),
+			Stream::Member(..)
+		));
+	}
 }
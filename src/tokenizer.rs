@@ -0,0 +1,250 @@
+use anyhow::{anyhow, Context, Result};
+use regex::Regex;
+use regex_syntax::hir::{Class, Hir, HirKind};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::dictionary::Dictionary;
+
+/// Characters that `char::is_whitespace` matches, that the text is split after.
+const WHITESPACE: &[char] = &[
+	'\t', '\n', '\u{b}', '\u{c}', '\r', ' ', '\u{85}', '\u{a0}', '\u{1680}', '\u{2000}',
+	'\u{2001}', '\u{2002}', '\u{2003}', '\u{2004}', '\u{2005}', '\u{2006}', '\u{2007}',
+	'\u{2008}', '\u{2009}', '\u{200a}', '\u{2028}', '\u{2029}', '\u{202f}', '\u{205f}',
+	'\u{3000}',
+];
+
+/// Splits text into the words that are counted.
+#[derive(Debug, Clone)]
+pub enum Tokenizer {
+	/// Words are the matches of the regular expression.
+	///
+	/// If the second element is set, only the capture group with that index is counted.
+	Regex(Regex, Option<usize>),
+	/// Words are found by the Unicode word boundary rules (UAX #29).
+	///
+	/// Only the segments that contain an alphanumeric character are counted.
+	Unicode,
+	/// Scripts that are written without spaces are segmented with the dictionary, the rest of the
+	/// text is tokenized by the second element.
+	Dictionary(Dictionary, Box<Tokenizer>),
+}
+
+impl Tokenizer {
+	/// Creates a `Tokenizer` from a regular expression given by the user.
+	///
+	/// `group` is either the name or the index of the capture group to count.
+	pub fn from_user_regex(pattern: &str, group: Option<&str>) -> Result<Self> {
+		let regex = Regex::new(pattern).context("Invalid --regex pattern")?;
+
+		let group = match group {
+			None => None,
+			Some(g) => {
+				let index = match g.parse::<usize>() {
+					Ok(i) if i < regex.captures_len() => i,
+					Ok(_) => {
+						return Err(anyhow!(
+							"--regex has no capture group {}",
+							g
+						))
+					}
+					Err(_) => regex
+						.capture_names()
+						.position(|n| n == Some(g))
+						.ok_or_else(|| {
+							anyhow!("--regex has no capture group named {}", g)
+						})?,
+				};
+
+				Some(index)
+			}
+		};
+
+		Ok(Tokenizer::Regex(regex, group))
+	}
+
+	/// Checks if the text can be split after a whitespace, and the pieces tokenized on their own,
+	/// with the same words as the whole text.
+	///
+	/// Regular expressions with anchors, such as `^` and `\A`, or that can match a whitespace
+	/// can't, as they would match at the start of every piece, or miss the words that span them.
+	pub fn is_splittable(&self) -> bool {
+		match self {
+			Tokenizer::Regex(regex, _) => match regex_syntax::parse(regex.as_str()) {
+				Ok(hir) => {
+					!hir.properties().look_set().contains_anchor()
+						&& !Self::matches_whitespace(&hir)
+				}
+				Err(_) => false,
+			},
+			Tokenizer::Unicode => true,
+			Tokenizer::Dictionary(_, other) => other.is_splittable(),
+		}
+	}
+
+	/// Checks if any part of `hir` can match a whitespace.
+	fn matches_whitespace(hir: &Hir) -> bool {
+		match hir.kind() {
+			HirKind::Empty | HirKind::Look(_) => false,
+			HirKind::Literal(l) => String::from_utf8_lossy(&l.0).contains(WHITESPACE),
+			HirKind::Class(Class::Unicode(c)) => c.ranges().iter().any(|r| {
+				WHITESPACE.iter().any(|w| (r.start()..=r.end()).contains(w))
+			}),
+			// Non-ASCII bytes may be a part of a whitespace.
+			HirKind::Class(Class::Bytes(c)) => c.ranges().iter().any(|r| {
+				!r.end().is_ascii()
+					|| WHITESPACE.iter().filter(|w| w.is_ascii()).any(|w| {
+						(r.start()..=r.end()).contains(&(*w as u8))
+					})
+			}),
+			HirKind::Repetition(r) => Self::matches_whitespace(&r.sub),
+			HirKind::Capture(c) => Self::matches_whitespace(&c.sub),
+			HirKind::Concat(hirs) | HirKind::Alternation(hirs) => {
+				hirs.iter().any(Self::matches_whitespace)
+			}
+		}
+	}
+
+	/// Calls `f` with every word in `s`.
+	pub fn for_each_token<'a, F>(&self, s: &'a str, mut f: F)
+	where
+		F: FnMut(&'a str),
+	{
+		self.tokens(s, &mut f);
+	}
+
+	/// Calls `f` with every word in `s`.
+	///
+	/// Takes a trait object, as `Tokenizer::Dictionary` calls it recursively.
+	fn tokens<'a>(&self, s: &'a str, f: &mut dyn FnMut(&'a str)) {
+		match self {
+			// Empty matches are not words, patterns such as `a*` match the empty string
+			// between every word.
+			Tokenizer::Regex(regex, None) => {
+				for m in regex.find_iter(s).filter(|m| !m.is_empty()) {
+					f(m.as_str());
+				}
+			}
+			Tokenizer::Regex(regex, Some(group)) => {
+				for m in regex
+					.captures_iter(s)
+					.filter_map(|c| c.get(*group))
+					.filter(|m| !m.is_empty())
+				{
+					f(m.as_str());
+				}
+			}
+			Tokenizer::Unicode => {
+				for w in s.unicode_words() {
+					f(w);
+				}
+			}
+			Tokenizer::Dictionary(dict, other) => {
+				dict.for_each_token(s, f, |o, f| other.tokens(o, *f));
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use clap::ValueEnum;
+
+	use super::*;
+	use crate::args::WordRegex;
+
+	fn tokens(tokenizer: &Tokenizer, s: &str) -> Vec<String> {
+		let mut res = Vec::new();
+		tokenizer.for_each_token(s, |t| res.push(t.to_string()));
+
+		res
+	}
+
+	#[test]
+	fn user_regex1() {
+		let tokenizer =
+			Tokenizer::from_user_regex(r"[A-Za-z_][A-Za-z0-9_]*", None).unwrap();
+
+		assert_eq!(
+			tokens(&tokenizer, "let foo_bar = 3 + baz2;"),
+			vec!["let", "foo_bar", "baz2"]
+		);
+	}
+
+	#[test]
+	fn user_regex2() {
+		let tokenizer = Tokenizer::from_user_regex(r"#(?<tag>\w+)", Some("tag")).unwrap();
+
+		assert_eq!(
+			tokens(&tokenizer, "lorem #ipsum dolor #sit_amet"),
+			vec!["ipsum", "sit_amet"]
+		);
+	}
+
+	#[test]
+	fn user_regex3() {
+		let tokenizer = Tokenizer::from_user_regex(r"(\w+)@(\w+)", Some("2")).unwrap();
+
+		assert_eq!(
+			tokens(&tokenizer, "lorem@ipsum dolor@sit"),
+			vec!["ipsum", "sit"]
+		);
+	}
+
+	#[test]
+	fn user_regex_empty() {
+		let tokenizer = Tokenizer::from_user_regex(r"a*", None).unwrap();
+		assert_eq!(tokens(&tokenizer, "aa b aaa"), vec!["aa", "aaa"]);
+
+		let tokenizer = Tokenizer::from_user_regex(r"b(a*)", Some("1")).unwrap();
+		assert_eq!(tokens(&tokenizer, "ba b baa"), vec!["a", "aa"]);
+	}
+
+	#[test]
+	fn splittable() {
+		for p in [r"\w+", r"\S+", r"#(?<tag>\w+)", r"\bfoo\b", r"[^\s,]+"] {
+			let tokenizer = Tokenizer::from_user_regex(p, None).unwrap();
+			assert!(tokenizer.is_splittable(), "{}", p);
+		}
+
+		for p in [
+			r"^\w+",
+			r"\A\w+",
+			r"(?m)\w+$",
+			r"\w+ \w+",
+			r"\w+\s\w+",
+			r"[^,]+",
+			r".+",
+		] {
+			let tokenizer = Tokenizer::from_user_regex(p, None).unwrap();
+			assert!(!tokenizer.is_splittable(), "{}", p);
+		}
+
+		for p in WordRegex::value_variants() {
+			assert!(p.to_tokenizer().is_splittable());
+		}
+		assert!(Tokenizer::Unicode.is_splittable());
+	}
+
+	#[test]
+	fn unicode1() {
+		assert_eq!(
+			tokens(&Tokenizer::Unicode, "Don't stop, e.g. 3.14 or ömür!"),
+			vec!["Don't", "stop", "e.g", "3.14", "or", "ömür"]
+		);
+	}
+
+	#[test]
+	fn unicode2() {
+		assert_eq!(
+			tokens(&Tokenizer::Unicode, "ömür ğğğ 式様 2d3"),
+			vec!["ömür", "ğğğ", "式", "様", "2d3"]
+		);
+	}
+
+	#[test]
+	fn user_regex_invalid() {
+		assert!(Tokenizer::from_user_regex(r"(\w+", None).is_err());
+		assert!(Tokenizer::from_user_regex(r"(\w+)", Some("2")).is_err());
+		assert!(Tokenizer::from_user_regex(r"(?<tag>\w+)", Some("name")).is_err());
+	}
+}
@@ -0,0 +1,343 @@
+use std::fs;
+
+use anyhow::{Context, Result};
+use ignore::{overrides::OverrideBuilder, WalkBuilder};
+
+use crate::wprintln;
+
+/// Options that control how directories are traversed.
+#[derive(Debug, Clone)]
+pub struct WalkOptions {
+	/// Maximum depth to descend, `None` for unlimited.
+	pub max_depth: Option<usize>,
+	/// Follow symbolic links.
+	pub follow_links: bool,
+	/// Include hidden files and directories.
+	pub hidden: bool,
+	/// Count every file under a directory into a single column.
+	pub aggregate: bool,
+	/// Respect `.gitignore` and `.ignore` files.
+	pub respect_ignore: bool,
+	/// Globs that the files must match, every file matches if empty.
+	pub include: Vec<String>,
+	/// Globs that the files must not match.
+	pub exclude: Vec<String>,
+}
+
+/// Represents an input that is displayed as a single column in the result.
+#[derive(Debug)]
+pub enum Input {
+	/// Represents a single file, or Stdin if the path is `-`.
+	Path(String),
+	/// Represents every file under a directory, aggregated into a single column.
+	///
+	/// First element is the path to the directory, the second is the paths to the files.
+	Directory(String, Vec<String>),
+}
+
+impl Input {
+	/// Returns the paths of the files that the `Input` consists of.
+	pub fn files(&self) -> &[String] {
+		match self {
+			Input::Path(p) => std::slice::from_ref(p),
+			Input::Directory(_, files) => files,
+		}
+	}
+}
+
+/// Resolves the paths given by the user into `Input`s.
+///
+/// If `walk` is `None`, every path is returned as is. If not, directories are traversed and
+/// either every regular file under them is returned as a seperate `Input`, or all of them
+/// are returned as a single `Input::Directory`.
+///
+/// Ignore files and include/exclude globs only apply to the files found while traversing,
+/// paths given explicitly are always returned. Include globs take precedence over the ignore
+/// files, and exclude globs over the include globs.
+pub fn resolve_inputs(paths: &[String], walk: Option<&WalkOptions>) -> Result<Vec<Input>> {
+	let Some(opts) = walk else {
+		return Ok(paths.iter().map(|p| Input::Path(p.clone())).collect());
+	};
+
+	let mut inputs = Vec::new();
+
+	for path in paths {
+		let is_dir = path != "-" && fs::metadata(path).is_ok_and(|m| m.is_dir());
+
+		if !is_dir {
+			inputs.push(Input::Path(path.clone()));
+			continue;
+		}
+
+		let files = walk_dir(path, opts)?;
+
+		if opts.aggregate {
+			inputs.push(Input::Directory(path.clone(), files));
+		} else {
+			inputs.extend(files.into_iter().map(Input::Path));
+		}
+	}
+
+	Ok(inputs)
+}
+
+/// Returns the paths of every regular file under the directory `path`, sorted by file name.
+fn walk_dir(path: &str, opts: &WalkOptions) -> Result<Vec<String>> {
+	let mut overrides = OverrideBuilder::new(path);
+
+	for glob in &opts.include {
+		overrides
+			.add(glob)
+			.with_context(|| format!("Invalid --include glob: {}", glob))?;
+	}
+	for glob in &opts.exclude {
+		overrides
+			.add(&format!("!{}", glob))
+			.with_context(|| format!("Invalid --exclude glob: {}", glob))?;
+	}
+
+	let walker = WalkBuilder::new(path)
+		.max_depth(opts.max_depth)
+		.follow_links(opts.follow_links)
+		.standard_filters(opts.respect_ignore)
+		.hidden(!opts.hidden)
+		.require_git(false)
+		.overrides(overrides.build()?)
+		.sort_by_file_name(|a, b| a.cmp(b))
+		.build();
+
+	let mut files = Vec::new();
+
+	for entry in walker {
+		let entry = match entry {
+			Ok(entry) => entry,
+			Err(e) => {
+				wprintln!("{}: {}", path, e);
+				continue;
+			}
+		};
+
+		if !entry.file_type().is_some_and(|t| t.is_file()) {
+			continue;
+		}
+
+		match entry.path().to_str() {
+			Some(p) => files.push(p.to_string()),
+			None => {
+				wprintln!("{}: Path is not valid UTF-8", entry.path().display());
+			}
+		}
+	}
+
+	Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::path::Path;
+
+	use tempfile::TempDir;
+
+	use super::*;
+
+	fn options() -> WalkOptions {
+		WalkOptions {
+			max_depth: None,
+			follow_links: false,
+			hidden: false,
+			aggregate: false,
+			respect_ignore: true,
+			include: Vec::new(),
+			exclude: Vec::new(),
+		}
+	}
+
+	/// Creates a directory with the given files in it.
+	fn tree(files: &[&str]) -> TempDir {
+		let dir = tempfile::tempdir().unwrap();
+
+		for f in files {
+			let path = dir.path().join(f);
+			fs::create_dir_all(path.parent().unwrap()).unwrap();
+			fs::write(path, "lorem ipsum").unwrap();
+		}
+
+		dir
+	}
+
+	fn root(dir: &TempDir) -> String {
+		dir.path().to_str().unwrap().to_string()
+	}
+
+	/// Walks `dir` and returns the paths of the files relative to it.
+	fn walk(dir: &TempDir, opts: &WalkOptions) -> Vec<String> {
+		let inputs = resolve_inputs(&[root(dir)], Some(opts)).unwrap();
+
+		inputs.iter()
+			.flat_map(Input::files)
+			.map(|f| {
+				let rel = Path::new(f).strip_prefix(dir.path()).unwrap();
+				rel.to_str().unwrap().replace('\\', "/")
+			})
+			.collect()
+	}
+
+	#[test]
+	fn no_walk() {
+		let paths = vec![String::from("-"), String::from("dir")];
+		let inputs = resolve_inputs(&paths, None).unwrap();
+
+		assert!(
+			matches!(&inputs[..], [Input::Path(a), Input::Path(b)] if a == "-" && b == "dir")
+		);
+	}
+
+	#[test]
+	fn sorted() {
+		let dir = tree(&["z.txt", "b/d/e.txt", "a.txt", "b/c.txt"]);
+
+		assert_eq!(
+			walk(&dir, &options()),
+			["a.txt", "b/c.txt", "b/d/e.txt", "z.txt"]
+		);
+	}
+
+	#[test]
+	fn max_depth() {
+		let dir = tree(&["a.txt", "b/c.txt", "b/d/e.txt"]);
+
+		let opts = WalkOptions {
+			max_depth: Some(1),
+			..options()
+		};
+		assert_eq!(walk(&dir, &opts), ["a.txt"]);
+
+		let opts = WalkOptions {
+			max_depth: Some(2),
+			..options()
+		};
+		assert_eq!(walk(&dir, &opts), ["a.txt", "b/c.txt"]);
+	}
+
+	#[test]
+	fn hidden() {
+		let dir = tree(&["a.txt", ".b.txt", ".c/d.txt"]);
+
+		assert_eq!(walk(&dir, &options()), ["a.txt"]);
+
+		let opts = WalkOptions {
+			hidden: true,
+			..options()
+		};
+		assert_eq!(walk(&dir, &opts), [".b.txt", ".c/d.txt", "a.txt"]);
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn follow_links() {
+		let dir = tree(&["a.txt"]);
+		let target = tree(&["b.txt"]);
+		std::os::unix::fs::symlink(target.path(), dir.path().join("link")).unwrap();
+
+		assert_eq!(walk(&dir, &options()), ["a.txt"]);
+
+		let opts = WalkOptions {
+			follow_links: true,
+			..options()
+		};
+		assert_eq!(walk(&dir, &opts), ["a.txt", "link/b.txt"]);
+	}
+
+	#[test]
+	fn aggregate() {
+		let dir = tree(&["a.txt", "b/c.txt"]);
+		let file = tree(&["d.txt"]);
+		let file = file.path().join("d.txt").to_str().unwrap().to_string();
+
+		let opts = WalkOptions {
+			aggregate: true,
+			..options()
+		};
+		let inputs = resolve_inputs(&[root(&dir), file.clone()], Some(&opts)).unwrap();
+
+		let [Input::Directory(d, files), Input::Path(p)] = &inputs[..] else {
+			panic!("unexpected inputs: {:?}", inputs);
+		};
+		assert_eq!(d, &root(&dir));
+		assert_eq!(files.len(), 2);
+		assert_eq!(p, &file);
+	}
+
+	#[test]
+	fn gitignore() {
+		let dir = tree(&["a.txt", "b.txt", "c/d.txt", "c/e.txt"]);
+		fs::write(dir.path().join(".gitignore"), "b.txt\n").unwrap();
+		fs::write(dir.path().join("c/.ignore"), "d.txt\n").unwrap();
+
+		assert_eq!(walk(&dir, &options()), ["a.txt", "c/e.txt"]);
+	}
+
+	#[test]
+	fn no_ignore() {
+		let dir = tree(&["a.txt", "b.txt", "c/d.txt"]);
+		fs::write(dir.path().join(".gitignore"), "b.txt\n").unwrap();
+		fs::write(dir.path().join(".ignore"), "c/\n").unwrap();
+
+		let opts = WalkOptions {
+			respect_ignore: false,
+			..options()
+		};
+		assert_eq!(walk(&dir, &opts), ["a.txt", "b.txt", "c/d.txt"]);
+	}
+
+	#[test]
+	fn include_exclude() {
+		let dir = tree(&["a.md", "a.txt", "b/c.md", "b/d.txt"]);
+
+		let opts = WalkOptions {
+			include: vec![String::from("*.md")],
+			..options()
+		};
+		assert_eq!(walk(&dir, &opts), ["a.md", "b/c.md"]);
+
+		let opts = WalkOptions {
+			exclude: vec![String::from("b")],
+			..options()
+		};
+		assert_eq!(walk(&dir, &opts), ["a.md", "a.txt"]);
+
+		// Exclude globs take precedence over the include globs.
+		let opts = WalkOptions {
+			include: vec![String::from("*.md")],
+			exclude: vec![String::from("a*")],
+			..options()
+		};
+		assert_eq!(walk(&dir, &opts), ["b/c.md"]);
+	}
+
+	#[test]
+	fn include_ignored() {
+		let dir = tree(&["a.md", "b.md", "c.txt"]);
+		fs::write(dir.path().join(".gitignore"), "b.md\n").unwrap();
+
+		assert_eq!(walk(&dir, &options()), ["a.md", "c.txt"]);
+
+		// Include globs take precedence over the ignore files.
+		let opts = WalkOptions {
+			include: vec![String::from("*.md")],
+			..options()
+		};
+		assert_eq!(walk(&dir, &opts), ["a.md", "b.md"]);
+	}
+
+	#[test]
+	fn invalid_glob() {
+		let dir = tree(&["a.txt"]);
+
+		let opts = WalkOptions {
+			include: vec![String::from("a{")],
+			..options()
+		};
+		assert!(resolve_inputs(&[root(&dir)], Some(&opts)).is_err());
+	}
+}